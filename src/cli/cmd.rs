@@ -1,17 +1,34 @@
-use clap::{Parser, Subcommand};
+use super::{completion, shell_init::InitShell};
+use clap::{ArgGroup, Parser, Subcommand};
+use clap_complete::{ArgValueCandidates, Shell};
 
 #[derive(Parser)]
 pub struct Cli {
     /// sub command
     #[command(subcommand)]
     pub command: Command,
-    /// alias setting file path
+    /// alias setting file path (user layer)
+    ///
+    /// merged over the system setting and beneath the nearest project `.alias-rs.toml`
     #[arg(long = "setting")]
     pub setting_path: Option<String>,
     /// runtime variable define, allow multiple inputs.
     /// e.g. --define var_1=xxx --define var_2=xxx
-    #[arg(long = "define")]
+    /// value may contain '=' and be quoted, a bare key takes its value from the environment
+    #[arg(long = "define", add = ArgValueCandidates::new(completion::complete_variables))]
     pub runtime_variables: Vec<String>,
+    /// runtime variables file, a `.env` file or a `.toml` file
+    ///
+    /// `--define` takes precedence over it, and it over the setting `[variables]`
+    #[arg(long = "define-file")]
+    pub define_file: Option<String>,
+    /// wait for another running alias-rs to release the setting lock, up to SECONDS if given.
+    /// e.g. --wait or --wait=30
+    ///
+    /// without it a command changing aliases fails at once when the setting is locked,
+    /// naming the PID holding the lock
+    #[arg(long = "wait", value_name = "SECONDS", num_args = 0..=1, require_equals = true)]
+    pub wait: Option<Option<u64>>,
 }
 
 #[derive(Subcommand)]
@@ -26,14 +43,76 @@ pub enum Command {
         /// alias mapping command
         #[arg(index = 2)]
         command: String,
+        /// more aliases and commands, all set together or none of them.
+        /// e.g. set gs "git status" gl "git log"
+        #[arg(index = 3)]
+        more: Vec<String>,
     },
     /// remove alias
     Remove {
         /// alias which you want to remove
+        #[arg(add = ArgValueCandidates::new(completion::complete_aliases))]
         alias: String,
     },
+    /// edit alias command, or the whole user setting, in $VISUAL or $EDITOR
+    ///
+    /// the result is checked the same as `set` (or `check` for the setting) before anything
+    /// is changed, nothing is changed if the editor exits with non-zero or nothing is edited
+    #[command(group(ArgGroup::new("target").required(true).args(["alias", "setting"])))]
+    Edit {
+        /// alias which you want to edit, a new alias is created if it does not exist
+        #[arg(add = ArgValueCandidates::new(completion::complete_aliases))]
+        alias: Option<String>,
+        /// edit the whole user setting instead of an alias
+        #[arg(long = "setting")]
+        setting: bool,
+    },
+    /// rename alias, with its setting entry, metadata and history
+    Rename {
+        /// alias which you want to rename
+        #[arg(add = ArgValueCandidates::new(completion::complete_aliases))]
+        source: String,
+        /// new alias name, it should not exist
+        target: String,
+    },
+    /// copy alias to a new name, with its setting entry and metadata
+    Copy {
+        /// alias which you want to copy
+        #[arg(add = ArgValueCandidates::new(completion::complete_aliases))]
+        source: String,
+        /// new alias name, it should not exist
+        target: String,
+    },
+    /// run alias directly, without `init` and PATH setup, exit with its exit code
+    ///
+    /// e.g. in CI containers, cron or scripts where the shell profile is not sourced
+    Run {
+        /// alias which you want to run
+        #[arg(add = ArgValueCandidates::new(completion::complete_aliases))]
+        alias: String,
+        /// arguments passed to the alias command
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
     /// list aliases
     List {},
+    /// show alias define and which setting it came from
+    Show {
+        /// alias which you want to show
+        #[arg(add = ArgValueCandidates::new(completion::complete_aliases))]
+        alias: String,
+    },
+    /// search aliases by name, command, description and tags, best match first
+    Search {
+        /// text to search, fuzzy matched by default
+        query: String,
+        /// match the query as a substring, ignoring case
+        #[arg(long = "exact", conflicts_with = "regex")]
+        exact: bool,
+        /// match the query as a regular expression
+        #[arg(long = "regex")]
+        regex: bool,
+    },
     /// export aliases define
     Export {
         /// export path (include file name)
@@ -41,4 +120,106 @@ pub enum Command {
     },
     /// import aliases define
     Import {},
+    /// show resolved variables and where each came from
+    Vars {},
+    /// show the journal of operations changing aliases, newest first
+    History {
+        /// show the last N operations only
+        #[arg(long = "limit", value_name = "N")]
+        limit: Option<usize>,
+    },
+    /// revert the last operations changing aliases (set, remove, import and init)
+    ///
+    /// every file and environment variable they changed is restored,
+    /// it fails if any of them was changed by something else since
+    Undo {
+        /// number of operations to revert
+        #[arg(default_value_t = 1)]
+        count: usize,
+    },
+    /// show the command versions of an alias, newest first
+    Log {
+        /// alias which you want to show
+        #[arg(add = ArgValueCandidates::new(completion::complete_aliases))]
+        alias: String,
+    },
+    /// set an alias back to an earlier command version, see `log`
+    Revert {
+        /// alias which you want to revert
+        #[arg(add = ArgValueCandidates::new(completion::complete_aliases))]
+        alias: String,
+        /// version to revert to, default is the one before the latest
+        version: Option<u32>,
+    },
+    /// archive the setting, all alias scripts and the shell profile block (or user env vars)
+    Backup {
+        /// backup file path, default is `alias-rs-backup-<timestamp>.tar.zst` in current dir
+        #[arg(long = "output", short = 'o')]
+        output: Option<String>,
+    },
+    /// restore a backup made by `backup`, it can be reverted by `undo`
+    ///
+    /// checksums are verified before anything is changed,
+    /// scripts not in the backup are removed
+    Restore {
+        /// backup file path
+        path: String,
+        /// overwrite the current state even if it was changed after the backup
+        #[arg(long = "force")]
+        force: bool,
+    },
+    /// create a setting file from the commented template
+    NewSetting {
+        /// setting file path, default is the user setting
+        path: Option<String>,
+    },
+    /// check setting file and the files it includes, exit with non-zero if any error found
+    Check {
+        /// setting file path, default is the user setting
+        path: Option<String>,
+    },
+    /// print JSON Schema of the setting file, for editor validation and completion
+    ///
+    /// e.g. alias-rs schema > alias-setting.schema.json,
+    /// then add "#:schema ./alias-setting.schema.json" as the first line of the setting (taplo)
+    Schema {},
+    /// convert setting file between TOML, YAML and JSON, format is detected from the extension
+    Convert {
+        /// source setting file path
+        source: String,
+        /// target setting file path, e.g. alias-setting.yaml
+        target: String,
+    },
+    /// upgrade setting file to the current setting version, keeping a backup
+    Migrate {
+        /// setting file path, default is the user setting
+        path: Option<String>,
+    },
+    /// browse aliases in a full-screen terminal UI, with fuzzy search over names, commands,
+    /// descriptions and tags, and a preview of the generated script
+    ///
+    /// keys: Enter run, Ctrl-E edit, Ctrl-R rename, Ctrl-D remove, Esc quit
+    Tui {},
+    /// manage aliases in an interactive shell, with line editing and history
+    ///
+    /// `set`, `remove`, `list`, `show`, `edit` and `run` work on the setting loaded once,
+    /// changes are applied in a single transaction on `commit` or `exit`
+    Shell {},
+    /// print shell code to eval in the shell profile, it adds the script home to PATH and makes
+    /// each alias complete the same as the command it wraps, e.g. `gco` as `git checkout`
+    ///
+    /// e.g. add `eval "$(alias-rs shell-init bash)"` to ~/.bashrc,
+    /// or `alias-rs shell-init fish | source` to ~/.config/fish/config.fish
+    ShellInit {
+        /// shell to init
+        shell: InitShell,
+    },
+    /// print shell completion, alias names and `--define` keys are completed from the setting
+    ///
+    /// e.g. add `source <(alias-rs completions bash)` to ~/.bashrc,
+    /// or `alias-rs completions powershell | Out-String | Invoke-Expression` to $PROFILE
+    Completions {
+        /// shell to complete
+        shell: Shell,
+    },
 }
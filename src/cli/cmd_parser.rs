@@ -1,12 +1,37 @@
-use super::cmd::{Cli, Command::*};
+use super::{
+    cmd::{Cli, Command::*},
+    completion, repl, shell_init, tui,
+};
 use crate::{
-    core::error::{AliasError, ErrorKind},
-    support::factory::{get_alias, get_alias_importer},
+    cmn::{editor, strings},
+    core::{
+        alias::Alias,
+        alias_history,
+        alias_search::{self, Field, Query, SearchMode},
+        alias_setting::{self, AliasSetting},
+        backup,
+        error::{AliasError, ErrorKind},
+        journal::{self, Entry, Journal},
+        lock,
+        migration::SETTING_VERSION,
+        setting_check::{self, Severity},
+        setting_index::SettingIndex,
+        transaction::Transaction,
+        variable::{unquote, RuntimeVariables},
+    },
+    support::factory::{
+        get_alias, get_alias_importer, get_setting_path, get_system_setting_path, run_alias,
+    },
 };
 use clap::Parser;
 use std::{
     collections::HashMap,
     env::{self, consts::OS},
+    fs,
+    io::{self, IsTerminal},
+    path::Path,
+    process,
+    time::{Duration, UNIX_EPOCH},
 };
 
 pub fn parse() -> Result<(), AliasError> {
@@ -20,27 +45,243 @@ pub fn parse() -> Result<(), AliasError> {
 
     let cli = Cli::parse();
     let setting_path = cli.setting_path;
-    let runtime_variables = runtime_variables_vec_to_map(cli.runtime_variables)?;
+    let lock_wait = cli.wait.map(|seconds| match seconds {
+        Some(seconds) => Duration::from_secs(seconds),
+        None => Duration::MAX,
+    });
+    let runtime_variables = RuntimeVariables::new(
+        runtime_variables_vec_to_map(cli.runtime_variables)?,
+        cli.define_file,
+    )?;
 
+    // only the commands changing the setting or the scripts rewrite the index,
+    // the shell and the tui rewrite it on each change themselves
+    let mutating = matches!(
+        cli.command,
+        Init {}
+            | Set { .. }
+            | Remove { .. }
+            | Edit { .. }
+            | Rename { .. }
+            | Copy { .. }
+            | Import {}
+            | Undo { .. }
+            | Revert { .. }
+            | Restore { .. }
+            | Migrate { .. }
+    );
     match cli.command {
         Init {} => {
+            alias_setting::create_default_if_missing(&get_setting_path(&setting_path))?;
             let alias_impl = get_alias(&setting_path, &runtime_variables)?.unwrap();
-            alias_impl.init()?;
+            let _lock = alias_impl.lock(lock_wait)?;
+            let mut tx = Transaction::new();
+            alias_impl.stage_init(&mut tx)?;
+            alias_impl.commit(tx, "init".to_owned())?;
         }
-        Set { alias, command } => {
+        Set {
+            alias,
+            command,
+            more,
+        } => {
+            if more.len() % 2 != 0 {
+                return Err(AliasError {
+                    kind: ErrorKind::Unkonw,
+                    msg: format!("alias without command :: {}", more.last().unwrap()),
+                });
+            }
+            alias_setting::create_default_if_missing(&get_setting_path(&setting_path))?;
             let alias_impl = get_alias(&setting_path, &runtime_variables)?.unwrap();
-            alias_impl.set(alias, command)?;
+            let _lock = alias_impl.lock(lock_wait)?;
+            let mut tx = Transaction::new();
+            let mut operation = format!("set {} {:?}", alias, command);
+            alias_impl.stage_set(&mut tx, &alias, &command)?;
+            for pair in more.chunks(2) {
+                alias_impl.stage_set(&mut tx, &pair[0], &pair[1])?;
+                operation.push_str(&format!(" {} {:?}", pair[0], pair[1]));
+            }
+            alias_impl.commit(tx, operation)?;
         }
         Remove { alias } => {
             let alias_impl = get_alias(&setting_path, &runtime_variables)?.unwrap();
-            alias_impl.remove(alias)?;
+            let _lock = alias_impl.lock(lock_wait)?;
+            let mut tx = Transaction::new();
+            alias_impl.stage_remove(&mut tx, &alias)?;
+            alias_impl.commit(tx, format!("remove {}", alias))?;
         }
-        List {} => {
+        Edit {
+            alias: Some(alias), ..
+        } => {
+            let alias_impl = get_alias(&setting_path, &runtime_variables)?.unwrap();
+            // edit the command as written, not with its placeholders replaced
+            let command = alias_setting::raw_alias_command(
+                &Transaction::new(),
+                &alias_impl.setting(),
+                &alias_impl.setting_path(),
+                &alias,
+            )?;
+            let Some(edited) = edit_command(&Transaction::new(), alias_impl.as_ref(), &alias)?
+            else {
+                println!("nothing changed :: {}", alias);
+                return Ok(());
+            };
+            // reload, in case the alias was changed while editing
+            let alias_impl = get_alias(&setting_path, &runtime_variables)?.unwrap();
+            let _lock = alias_impl.lock(lock_wait)?;
+            let current = alias_setting::raw_alias_command(
+                &Transaction::new(),
+                &alias_impl.setting(),
+                &alias_impl.setting_path(),
+                &alias,
+            )?;
+            if current != command {
+                return Err(AliasError {
+                    kind: ErrorKind::Unkonw,
+                    msg: format!(
+                        "alias was changed while editing, edit it again :: {}",
+                        alias
+                    ),
+                });
+            }
+            let mut tx = Transaction::new();
+            alias_impl.stage_set(&mut tx, &alias, &edited)?;
+            alias_impl.commit(tx, format!("edit {}", alias))?;
+        }
+        Edit { .. } => {
+            let alias_impl = get_alias(&setting_path, &runtime_variables)?.unwrap();
+            let user_setting_path = alias_impl.setting_path();
+            let content = fs::read_to_string(&user_setting_path).map_err(|e| AliasError {
+                kind: ErrorKind::Unkonw,
+                msg: format!("read setting fail :: {} :: {}", user_setting_path, e),
+            })?;
+            // beside the setting, so its includes are found the same
+            let extension = Path::new(&user_setting_path)
+                .extension()
+                .map_or("toml".to_owned(), |e| e.to_string_lossy().to_string());
+            let temp_path = Path::new(&alias_setting::get_setting_dir(&user_setting_path))
+                .join(format!(".alias-rs-edit-{}.{}", process::id(), extension))
+                .to_string_lossy()
+                .to_string();
+            let edited = match edit_temp_file(&temp_path, content.clone()) {
+                Ok(edited) if edited != content => edited,
+                result => {
+                    let _ = fs::remove_file(&temp_path);
+                    result?;
+                    println!("nothing changed :: {}", user_setting_path);
+                    return Ok(());
+                }
+            };
+            let diagnostics = setting_check::check(&temp_path, &runtime_variables);
+            for diagnostic in &diagnostics {
+                println!("{}\n", diagnostic);
+            }
+            if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+                return Err(AliasError {
+                    kind: ErrorKind::Unkonw,
+                    msg: format!(
+                        "edited setting is invalid, nothing changed, the edit is kept at :: {}",
+                        temp_path
+                    ),
+                });
+            }
+            let new_setting = get_alias(&Some(temp_path.clone()), &runtime_variables)?
+                .unwrap()
+                .setting();
+            let _lock = alias_impl.lock(lock_wait)?;
+            if fs::read_to_string(&user_setting_path).ok().as_ref() != Some(&content) {
+                return Err(AliasError {
+                    kind: ErrorKind::Unkonw,
+                    msg: format!(
+                        "setting was changed while editing, nothing changed, the edit is kept at :: {}",
+                        temp_path
+                    ),
+                });
+            }
+            let mut tx = Transaction::new();
+            alias_impl.stage_setting_replace(&mut tx, edited.into_bytes(), &new_setting)?;
+            alias_impl.commit(tx, "edit --setting".to_owned())?;
+            let _ = fs::remove_file(&temp_path);
+        }
+        Rename { source, target } => {
+            let alias_impl = get_alias(&setting_path, &runtime_variables)?.unwrap();
+            let _lock = alias_impl.lock(lock_wait)?;
+            let mut tx = Transaction::new();
+            alias_impl.stage_copy(&mut tx, &source, &target, true)?;
+            alias_impl.commit(tx, format!("rename {} {}", source, target))?;
+        }
+        Copy { source, target } => {
             let alias_impl = get_alias(&setting_path, &runtime_variables)?.unwrap();
-            let list = alias_impl.list()?;
-            if let Some(list) = list {
-                for alias in list {
-                    println!("{}", alias);
+            let _lock = alias_impl.lock(lock_wait)?;
+            let mut tx = Transaction::new();
+            alias_impl.stage_copy(&mut tx, &source, &target, false)?;
+            alias_impl.commit(tx, format!("copy {} {}", source, target))?;
+        }
+        Run { alias, args } => {
+            let index = load_index(&setting_path, &runtime_variables)?;
+            let Some(indexed) = index.aliases.get(&alias) else {
+                let names: Vec<String> = index.aliases.keys().cloned().collect();
+                return Err(AliasError {
+                    kind: ErrorKind::Unkonw,
+                    msg: match strings::most_similar(&alias, &names) {
+                        Some(similar) => {
+                            format!("alias not found :: {} (did you mean {}?)", alias, similar)
+                        }
+                        None => format!("alias not found :: {}", alias),
+                    },
+                });
+            };
+            let code = run_alias(&alias, &indexed.command, &args)?;
+            process::exit(code);
+        }
+        List {} => {
+            let index = load_index(&setting_path, &runtime_variables)?;
+            for (alias, indexed) in &index.aliases {
+                println!("{}    ({})", alias, indexed.source);
+            }
+        }
+        Show { alias } => {
+            let index = load_index(&setting_path, &runtime_variables)?;
+            match index.aliases.get(&alias) {
+                Some(indexed) => {
+                    println!("{}={}    ({})", alias, indexed.command, indexed.source);
+                    if let Some(description) = &indexed.description {
+                        println!("description: {}", description);
+                    }
+                    if !indexed.tags.is_empty() {
+                        println!("tags: {}", indexed.tags.join(", "));
+                    }
+                }
+                None => {
+                    return Err(AliasError {
+                        kind: ErrorKind::Unkonw,
+                        msg: format!("alias not found in setting :: {}", alias),
+                    })
+                }
+            }
+        }
+        Search {
+            query,
+            exact,
+            regex,
+        } => {
+            let mode = match (exact, regex) {
+                (true, _) => SearchMode::Exact,
+                (_, true) => SearchMode::Regex,
+                _ => SearchMode::Fuzzy,
+            };
+            let query = Query::new(&query, mode)?;
+            let index = load_index(&setting_path, &runtime_variables)?;
+            let colored = io::stdout().is_terminal();
+            for result in alias_search::search(&index, &query) {
+                let indexed = &index.aliases[&result.alias];
+                for (field, text) in alias_search::fields(&result.alias, indexed) {
+                    let text = highlight(&text, result.indices(field), colored);
+                    match field {
+                        Field::Name => print!("{}", text),
+                        Field::Command => println!("={}    ({})", text, indexed.source),
+                        Field::Description => println!("    description: {}", text),
+                        Field::Tags => println!("    tags: {}", text),
+                    }
                 }
             }
         }
@@ -49,11 +290,344 @@ pub fn parse() -> Result<(), AliasError> {
         }
         Import {} => {
             let alias_importer = get_alias_importer(&setting_path, &runtime_variables)?.unwrap();
-            alias_importer.import()?;
+            alias_importer.import(lock_wait)?;
+        }
+        Vars {} => {
+            let alias_impl = get_alias(&setting_path, &runtime_variables)?.unwrap();
+            for (name, variable) in alias_impl.setting().resolved_variables {
+                println!("{}={}    ({})", name, variable.value, variable.source);
+            }
+        }
+        NewSetting { path } => {
+            let path = path.unwrap_or(get_setting_path(&setting_path));
+            change_setting_file(
+                &setting_path,
+                &path,
+                lock_wait,
+                format!("new-setting {}", path),
+                |tx| alias_setting::stage_new_setting(tx, &path),
+            )?;
+            println!("setting created :: {}", path);
+        }
+        Check { path } => {
+            let path = path.unwrap_or(get_setting_path(&setting_path));
+            let diagnostics = setting_check::check(&path, &runtime_variables);
+            for diagnostic in &diagnostics {
+                println!("{}\n", diagnostic);
+            }
+            let errors = diagnostics
+                .iter()
+                .filter(|d| d.severity == Severity::Error)
+                .count();
+            let warnings = diagnostics.len() - errors;
+            if errors > 0 {
+                return Err(AliasError {
+                    kind: ErrorKind::Unkonw,
+                    msg: format!(
+                        "setting check fail :: {} :: {} error(s), {} warning(s)",
+                        path, errors, warnings
+                    ),
+                });
+            }
+            println!("setting is valid :: {} :: {} warning(s)", path, warnings);
+        }
+        History { limit } => {
+            let alias_impl = get_alias(&setting_path, &runtime_variables)?.unwrap();
+            let entries = alias_impl.journal().entries()?;
+            let undone: HashMap<u64, u64> = entries
+                .iter()
+                .flat_map(|e| e.undoes.iter().map(|id| (*id, e.id)))
+                .collect();
+            for entry in entries.iter().rev().take(limit.unwrap_or(usize::MAX)) {
+                let time =
+                    humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(entry.time));
+                match undone.get(&entry.id) {
+                    Some(undo_id) => println!(
+                        "#{}    {}    {}    (undone by #{})",
+                        entry.id, time, entry.operation, undo_id
+                    ),
+                    None => println!("#{}    {}    {}", entry.id, time, entry.operation),
+                }
+                for record in &entry.files {
+                    let change = match (&record.before, &record.after) {
+                        (None, _) => "added",
+                        (_, None) => "removed",
+                        _ => "modified",
+                    };
+                    println!("    {} {}", change, record.path);
+                }
+                for record in &entry.envs {
+                    println!("    env {}", record.name);
+                }
+            }
+            return Ok(());
+        }
+        Undo { count } => {
+            let alias_impl = get_alias(&setting_path, &runtime_variables)?.unwrap();
+            let _lock = alias_impl.lock(lock_wait)?;
+            let journal = alias_impl.journal();
+            let entries = journal.entries()?;
+            let undoable: Vec<&Entry> = journal::undoable(&entries)
+                .into_iter()
+                .take(count)
+                .collect();
+            if undoable.len() < count {
+                return Err(AliasError {
+                    kind: ErrorKind::Unkonw,
+                    msg: format!(
+                        "not enough operations to undo :: requested={}, undoable={}",
+                        count,
+                        undoable.len()
+                    ),
+                });
+            }
+            let mut tx = Transaction::new();
+            journal::stage_undo(&mut tx, &undoable)?;
+            let undoes: Vec<u64> = undoable.iter().map(|e| e.id).collect();
+            let operation = format!("undo {}", count);
+            journal.append(operation, undoes, tx.commit()?)?;
+            for entry in undoable {
+                println!("undone #{} {}", entry.id, entry.operation);
+            }
+        }
+        Log { alias } => {
+            let alias_impl = get_alias(&setting_path, &runtime_variables)?.unwrap();
+            let setting_dir = alias_setting::get_setting_dir(&alias_impl.setting_path());
+            let versions = alias_history::versions(&Transaction::new(), &setting_dir, &alias)?;
+            if versions.is_empty() {
+                println!("no history of alias :: {}", alias);
+            }
+            for version in versions.iter().rev() {
+                let time = humantime::format_rfc3339_seconds(
+                    UNIX_EPOCH + Duration::from_secs(version.time),
+                );
+                match &version.command {
+                    Some(command) => println!("v{}    {}    {}", version.version, time, command),
+                    None => println!("v{}    {}    (removed)", version.version, time),
+                }
+            }
+            return Ok(());
+        }
+        Revert { alias, version } => {
+            let alias_impl = get_alias(&setting_path, &runtime_variables)?.unwrap();
+            let _lock = alias_impl.lock(lock_wait)?;
+            let setting_dir = alias_setting::get_setting_dir(&alias_impl.setting_path());
+            let mut tx = Transaction::new();
+            let versions = alias_history::versions(&tx, &setting_dir, &alias)?;
+            let target = match version {
+                Some(version) => versions.iter().find(|v| v.version == version),
+                None => versions.iter().rev().nth(1),
+            };
+            let Some(target) = target else {
+                return Err(AliasError {
+                    kind: ErrorKind::Unkonw,
+                    msg: format!(
+                        "alias version not found, see `alias-rs log {}` :: {} :: version={}",
+                        alias,
+                        alias,
+                        version.map_or("previous".to_owned(), |v| v.to_string())
+                    ),
+                });
+            };
+            let Some(command) = target.command.clone() else {
+                return Err(AliasError {
+                    kind: ErrorKind::Unkonw,
+                    msg: format!(
+                        "alias was removed in this version, use `alias-rs remove` instead :: {} :: version={}",
+                        alias, target.version
+                    ),
+                });
+            };
+            let operation = format!("revert {} {}", alias, target.version);
+            alias_impl.stage_set(&mut tx, &alias, &command)?;
+            alias_impl.commit(tx, operation)?;
+            println!("{}={}    (v{})", alias, command, target.version);
+        }
+        Backup { output } => {
+            let alias_impl = get_alias(&setting_path, &runtime_variables)?.unwrap();
+            let _lock = alias_impl.lock(lock_wait)?;
+            let output = output.unwrap_or(backup::default_backup_name());
+            let count = backup::create(alias_impl.as_ref(), &output)?;
+            println!("backup :: {} :: {} file(s)", output, count);
+        }
+        Restore { path, force } => {
+            let alias_impl = get_alias(&setting_path, &runtime_variables)?.unwrap();
+            let _lock = alias_impl.lock(lock_wait)?;
+            let mut tx = Transaction::new();
+            backup::stage_restore(alias_impl.as_ref(), &mut tx, &path, force)?;
+            alias_impl.commit(tx, format!("restore {}", path))?;
+        }
+        Schema {} => {
+            let schema = schemars::schema_for!(AliasSetting);
+            let schema = serde_json::to_string_pretty(&schema).map_err(|e| AliasError {
+                kind: ErrorKind::Unkonw,
+                msg: format!("serialize setting schema fail :: {}", e),
+            })?;
+            // print schema only, so it can be redirected to a file
+            println!("{}", schema);
+            return Ok(());
+        }
+        Convert { source, target } => {
+            change_setting_file(
+                &setting_path,
+                &target,
+                lock_wait,
+                format!("convert {} {}", source, target),
+                |tx| alias_setting::stage_convert(tx, &source, &target),
+            )?;
+        }
+        Tui {} => {
+            tui::start(&setting_path, &runtime_variables, lock_wait)?;
+            return Ok(());
+        }
+        Shell {} => {
+            repl::start(&setting_path, &runtime_variables, lock_wait)?;
+        }
+        ShellInit { shell } => {
+            let index = load_index(&setting_path, &runtime_variables)?;
+            // print shell code only, so it can be evaluated
+            print!("{}", shell_init::render(shell, &index));
+            return Ok(());
+        }
+        Completions { shell } => {
+            // print completion only, so it can be sourced
+            completion::print_registration(shell)?;
+            return Ok(());
+        }
+        Migrate { path } => {
+            let path = path.unwrap_or(get_setting_path(&setting_path));
+            let migrated = change_setting_file(
+                &setting_path,
+                &path,
+                lock_wait,
+                format!("migrate {}", path),
+                |tx| alias_setting::stage_migrate(tx, &path),
+            )?;
+            match migrated {
+                Some((version, backup_path)) => println!(
+                    "migrated :: {} :: version {} -> {} :: backup {}",
+                    path, version, SETTING_VERSION, backup_path
+                ),
+                None => println!("setting is up to date :: {}", path),
+            }
+        }
+    }
+    if mutating {
+        // the command has released its lock by now, take it again while indexing, so a
+        // concurrent change either lands before the setting is loaded for the index or waits
+        if let Ok(Some(alias_impl)) = get_alias(&setting_path, &runtime_variables) {
+            if let Ok(_lock) = alias_impl.lock(lock_wait) {
+                refresh_index(&setting_path, &runtime_variables);
+            }
         }
     }
     println!("done");
-    return Ok(());
+    Ok(())
+}
+
+/// Index of the loaded setting, rebuilt from the setting files if stale
+pub(super) fn load_index(
+    setting_path: &Option<String>,
+    runtime_variables: &RuntimeVariables,
+) -> Result<SettingIndex, AliasError> {
+    let index = SettingIndex::load(
+        &get_system_setting_path(),
+        &get_setting_path(setting_path),
+        runtime_variables,
+    );
+    if let Some(index) = index {
+        return Ok(index);
+    }
+    // only changes write the index, under the lock, a stale one is rebuilt in memory
+    let alias_impl = get_alias(setting_path, runtime_variables)?.unwrap();
+    Ok(SettingIndex::new(
+        &alias_impl.setting(),
+        &alias_impl.setting_path(),
+    ))
+}
+
+/// Rewrite the setting index and its copy in the script home after a change, if the setting
+/// can be loaded. Call it with the setting locked, it loads the setting again to index what is
+/// on disk. The copy is built from the system and user setting only, as multicall links run
+/// from any directory and without runtime variables
+pub(super) fn refresh_index(setting_path: &Option<String>, runtime_variables: &RuntimeVariables) {
+    let Ok(Some(alias_impl)) = get_alias(setting_path, runtime_variables) else {
+        return;
+    };
+    let user_setting_path = alias_impl.setting_path();
+    let setting = alias_impl.setting();
+    let _ = SettingIndex::new(&setting, &user_setting_path).write(&user_setting_path);
+    if let Ok(mut global_setting) = alias_setting::load_without_project(
+        &get_system_setting_path(),
+        &user_setting_path,
+        &RuntimeVariables::default(),
+    ) {
+        global_setting.script.home = setting.script.home;
+        let _ = SettingIndex::new(&global_setting, &user_setting_path).write_script_home();
+    }
+}
+
+/// Change setting files which are not loaded as an alias, locked together with the user
+/// setting and journaled there, so they can be undone like the other changes
+fn change_setting_file<T>(
+    setting_path: &Option<String>,
+    path: &str,
+    lock_wait: Option<Duration>,
+    operation: String,
+    stage: impl FnOnce(&mut Transaction) -> Result<T, AliasError>,
+) -> Result<T, AliasError> {
+    let setting_dir = alias_setting::get_setting_dir(&get_setting_path(setting_path));
+    let _locks = lock::lock_dirs(
+        &[setting_dir.clone(), alias_setting::get_setting_dir(path)],
+        lock_wait,
+    )?;
+    let mut tx = Transaction::new();
+    let staged = stage(&mut tx)?;
+    Journal::new(&setting_dir).append(operation, Vec::new(), tx.commit()?)?;
+    Ok(staged)
+}
+
+/// Open the command of an alias in the editor, as written in the setting with the changes
+/// staged in `tx`, so its placeholders are kept. Return the edited command or `None` if it
+/// is not changed
+pub(super) fn edit_command(
+    tx: &Transaction,
+    alias_impl: &dyn Alias,
+    alias: &str,
+) -> Result<Option<String>, AliasError> {
+    let command = alias_setting::raw_alias_command(
+        tx,
+        &alias_impl.setting(),
+        &alias_impl.setting_path(),
+        alias,
+    )?
+    .unwrap_or_default();
+    // a new file with a random name, readable by the user only, removed when dropped
+    let temp_file = tempfile::Builder::new()
+        .prefix("alias-rs-edit-")
+        .suffix(".txt")
+        .tempfile()
+        .map_err(|e| AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!("create temp file fail :: {} :: {}", alias, e),
+        })?;
+    let temp_path = temp_file.path().to_string_lossy().to_string();
+    let edited = edit_temp_file(&temp_path, command.clone())?;
+    let edited = edited.trim_end_matches(['\r', '\n']);
+    Ok((edited != command).then(|| edited.to_owned()))
+}
+
+/// Write content to a temp file, open it in the editor, return the edited content
+pub(super) fn edit_temp_file(temp_path: &String, content: String) -> Result<String, AliasError> {
+    fs::write(temp_path, content).map_err(|e| AliasError {
+        kind: ErrorKind::Unkonw,
+        msg: format!("write temp file fail :: {} :: {}", temp_path, e),
+    })?;
+    editor::open(temp_path)?;
+    fs::read_to_string(temp_path).map_err(|e| AliasError {
+        kind: ErrorKind::Unkonw,
+        msg: format!("read temp file fail :: {} :: {}", temp_path, e),
+    })
 }
 
 fn support_target_os() -> bool {
@@ -65,17 +639,104 @@ fn runtime_variables_vec_to_map(
 ) -> Result<HashMap<String, String>, AliasError> {
     let mut map: HashMap<String, String> = HashMap::new();
     for kv in kv_variables {
-        let split: Vec<&str> = kv.split('=').collect();
-        if split.len() != 2 {
+        // only the first '=' separates key and value, e.g. --define opts=--color=auto
+        let (key, value) = match kv.split_once('=') {
+            Some((key, value)) => (key.trim(), Some(unquote(value).to_owned())),
+            None => (kv.trim(), None),
+        };
+        if key.is_empty() {
             return Err(AliasError {
                 kind: ErrorKind::Unkonw,
-                msg: format!(
-                    "runtime variables define should be like \"--define key=value\" :: {}",
-                    kv
-                ),
+                msg: format!("runtime variable name should not be empty :: {}", kv),
             });
         }
-        map.insert(split[0].to_owned(), split[1].to_owned());
+        // --define key, take the value from the process environment
+        let value = match value {
+            Some(value) => value,
+            None => env::var(key).map_err(|_| AliasError {
+                kind: ErrorKind::Unkonw,
+                msg: format!(
+                    "runtime variable not found in environment, define it like \"--define key=value\" :: {}",
+                    key
+                ),
+            })?,
+        };
+        map.insert(key.to_owned(), value);
     }
     Ok(map)
 }
+
+/// Text with the chars at `indices` in bold yellow, plain if not colored (e.g. piped)
+fn highlight(text: &str, indices: &[usize], colored: bool) -> String {
+    if !colored {
+        return text.to_owned();
+    }
+    let mut highlighted = String::new();
+    let mut in_match = false;
+    for (i, c) in text.chars().enumerate() {
+        if indices.contains(&i) != in_match {
+            in_match = !in_match;
+            highlighted.push_str(if in_match { "\x1b[1;33m" } else { "\x1b[0m" });
+        }
+        highlighted.push(c);
+    }
+    if in_match {
+        highlighted.push_str("\x1b[0m");
+    }
+    highlighted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn define(kv: &[&str]) -> Result<HashMap<String, String>, AliasError> {
+        runtime_variables_vec_to_map(kv.iter().map(|kv| kv.to_string()).collect())
+    }
+
+    #[test]
+    fn define_key_value() {
+        let map = define(&[
+            "ctx=prod",
+            "opts=--color=auto",
+            "quoted=\"a b\"",
+            " spaced =v",
+        ])
+        .unwrap();
+        assert_eq!(map["ctx"], "prod");
+        assert_eq!(map["opts"], "--color=auto");
+        assert_eq!(map["quoted"], "a b");
+        assert_eq!(map["spaced"], "v");
+    }
+
+    #[test]
+    fn define_key_from_env() {
+        env::set_var("ALIAS_RS_TEST_DEFINE_FROM_ENV", "from env");
+        let map = define(&["ALIAS_RS_TEST_DEFINE_FROM_ENV"]).unwrap();
+        assert_eq!(map["ALIAS_RS_TEST_DEFINE_FROM_ENV"], "from env");
+    }
+
+    #[test]
+    fn define_empty_key() {
+        for kv in ["", " ", "=value"] {
+            let e = define(&[kv]).unwrap_err();
+            assert!(
+                e.msg
+                    .starts_with("runtime variable name should not be empty"),
+                "{}",
+                e.msg
+            );
+        }
+    }
+
+    #[test]
+    fn define_key_missing_in_env() {
+        let e = define(&["ALIAS_RS_TEST_DEFINE_MISSING"]).unwrap_err();
+        assert!(
+            e.msg
+                .starts_with("runtime variable not found in environment"),
+            "{}",
+            e.msg
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use super::{cmd::Cli, cmd_parser, multicall};
+use crate::core::{
+    error::{AliasError, ErrorKind},
+    setting_index::SettingIndex,
+    variable::RuntimeVariables,
+};
+use clap::CommandFactory;
+use clap_complete::{
+    env::{EnvCompleter, Shells},
+    CompleteEnv, CompletionCandidate, Shell,
+};
+use std::io;
+
+/// Environment variable the registered completion calls alias-rs with
+const COMPLETE_VAR: &str = "COMPLETE";
+const BIN_NAME: &str = "alias-rs";
+
+/// Answer a completion request from the registered completion and exit, if this is one
+pub fn complete() {
+    CompleteEnv::with_factory(Cli::command)
+        .var(COMPLETE_VAR)
+        .bin(BIN_NAME)
+        .complete();
+}
+
+/// Print the completion registration of a shell, to be sourced in its profile
+pub fn print_registration(shell: Shell) -> Result<(), AliasError> {
+    let name = shell.to_string();
+    let shells = Shells::builtins();
+    let completer = shells.completer(&name).ok_or_else(|| AliasError {
+        kind: ErrorKind::Unkonw,
+        msg: format!("unsupported completion shell :: {}", name),
+    })?;
+    write_registration(completer).map_err(|e| AliasError {
+        kind: ErrorKind::Unkonw,
+        msg: format!("write completion fail :: {} :: {}", name, e),
+    })
+}
+
+fn write_registration(completer: &dyn EnvCompleter) -> Result<(), io::Error> {
+    let binary_path = multicall::get_binary_path().unwrap_or_else(|_| BIN_NAME.to_owned());
+    completer.write_registration(
+        COMPLETE_VAR,
+        BIN_NAME,
+        BIN_NAME,
+        &binary_path,
+        &mut io::stdout(),
+    )
+}
+
+/// Index of the default user setting, as completion does not know `--setting` and `--define`
+fn load_index() -> Option<SettingIndex> {
+    cmd_parser::load_index(&None, &RuntimeVariables::default()).ok()
+}
+
+/// Alias names, with their descriptions or commands
+pub fn complete_aliases() -> Vec<CompletionCandidate> {
+    let Some(index) = load_index() else {
+        return Vec::new();
+    };
+    index
+        .aliases
+        .into_iter()
+        .map(|(alias, indexed)| {
+            let help = indexed.description.unwrap_or(indexed.command);
+            CompletionCandidate::new(alias).help(Some(help.into()))
+        })
+        .collect()
+}
+
+/// `--define` keys as `key=`, with their values in the setting
+pub fn complete_variables() -> Vec<CompletionCandidate> {
+    let Some(index) = load_index() else {
+        return Vec::new();
+    };
+    index
+        .variables
+        .into_iter()
+        .map(|(name, value)| {
+            CompletionCandidate::new(format!("{}=", name)).help(Some(value.into()))
+        })
+        .collect()
+}
@@ -1,4 +1,9 @@
 mod cmd;
 mod cmd_parser;
+pub mod completion;
+pub mod multicall;
+mod repl;
+mod shell_init;
+mod tui;
 
 pub use cmd_parser::parse;
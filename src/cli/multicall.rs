@@ -0,0 +1,73 @@
+use crate::{
+    core::{
+        error::{AliasError, ErrorKind},
+        setting_index::SettingIndex,
+    },
+    support::factory::{exec_alias, get_system_setting_path},
+};
+use std::{
+    env::{self, consts::OS},
+    path::{Path, PathBuf},
+};
+
+const BINARY_NAME: &str = "alias-rs";
+
+/// Path of the running alias-rs binary, which multicall links point to
+pub fn get_binary_path() -> Result<String, AliasError> {
+    env::current_exe()
+        .map(|path| path.to_string_lossy().to_string())
+        .map_err(|e| AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!("get alias-rs binary path fail :: {}", e),
+        })
+}
+
+/// Run an alias if alias-rs is invoked through a multicall link, return the exit code.
+/// `None` if invoked as alias-rs itself, or not through a link in a script home
+pub fn dispatch() -> Option<Result<i32, AliasError>> {
+    let mut args = env::args();
+    let invoked = PathBuf::from(args.next()?);
+    // the whole name, an alias name may contain '.'
+    let file_name = invoked.file_name()?.to_string_lossy().to_string();
+    let alias = match OS {
+        "windows" => file_name
+            .strip_suffix(".exe")
+            .unwrap_or(&file_name)
+            .to_owned(),
+        _ => file_name,
+    };
+    if alias == BINARY_NAME {
+        return None;
+    }
+    let link_path = find_link(&invoked)?;
+    let script_home = link_path.parent()?.to_string_lossy().to_string();
+    let index = match SettingIndex::load_script_home(&script_home, &get_system_setting_path()) {
+        Ok(index) => index?,
+        Err(e) => return Some(Err(e)),
+    };
+    let args: Vec<String> = args.collect();
+    Some(match index.aliases.get(&alias) {
+        Some(indexed) => exec_alias(&alias, &indexed.command, &args),
+        None => Err(AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!(
+                "alias not found in alias index, run `alias-rs import` to rebuild it :: {} :: {}",
+                alias, script_home
+            ),
+        }),
+    })
+}
+
+/// The link alias-rs is invoked through, searched in `PATH` if invoked by name only
+fn find_link(invoked: &Path) -> Option<PathBuf> {
+    if invoked.components().count() > 1 {
+        return Some(invoked.to_path_buf());
+    }
+    let file_name = match OS {
+        "windows" if invoked.extension().is_none() => format!("{}.exe", invoked.display()),
+        _ => invoked.to_string_lossy().to_string(),
+    };
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(&file_name))
+        .find(|path| path.symlink_metadata().is_ok())
+}
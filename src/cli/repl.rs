@@ -0,0 +1,283 @@
+use super::cmd_parser::{edit_command, refresh_index};
+use crate::{
+    core::{
+        alias::Alias,
+        alias_setting,
+        error::{AliasError, ErrorKind},
+        transaction::Transaction,
+        variable::{unquote, RuntimeVariables},
+    },
+    support::factory::{get_alias, run_alias},
+};
+use indexmap::IndexMap;
+use rustyline::{error::ReadlineError, DefaultEditor};
+use std::{path::Path, time::Duration};
+
+const HISTORY_FILE_NAME: &str = "shell-history.txt";
+const HELP: &str = "\
+set <alias> <command>    set alias, the command may be quoted
+remove <alias>           remove alias
+list                     list aliases, with changes not applied yet
+show <alias>             show alias command
+edit <alias>             edit alias command in $VISUAL or $EDITOR
+run <alias> [args...]    run alias
+commit                   apply the changes
+discard                  drop the changes not applied yet
+exit                     apply the changes and quit, the same as Ctrl-D
+abort                    quit without applying the changes";
+
+/// What to do after a line
+enum Flow {
+    Continue,
+    Exit,
+    Abort,
+}
+
+/// Changes made in the shell, staged in one transaction until applied
+struct Session {
+    alias_impl: Box<dyn Alias>,
+    setting_path: Option<String>,
+    runtime_variables: RuntimeVariables,
+    lock_wait: Option<Duration>,
+    tx: Transaction,
+    /// aliases changed in the transaction, `None` if removed
+    pending: IndexMap<String, Option<String>>,
+    operations: Vec<String>,
+}
+
+/// Interactive alias management with line editing and history, the setting is loaded once,
+/// and changes are applied in a single transaction on `commit` or `exit`
+pub fn start(
+    setting_path: &Option<String>,
+    runtime_variables: &RuntimeVariables,
+    lock_wait: Option<Duration>,
+) -> Result<(), AliasError> {
+    let mut session = Session {
+        alias_impl: get_alias(setting_path, runtime_variables)?.unwrap(),
+        setting_path: setting_path.clone(),
+        runtime_variables: runtime_variables.clone(),
+        lock_wait,
+        tx: Transaction::new(),
+        pending: IndexMap::new(),
+        operations: Vec::new(),
+    };
+    let mut editor = DefaultEditor::new().map_err(|e| AliasError {
+        kind: ErrorKind::Unkonw,
+        msg: format!("open line editor fail :: {}", e),
+    })?;
+    let history_path = Path::new(&alias_setting::get_setting_dir(
+        &session.alias_impl.setting_path(),
+    ))
+    .join(HISTORY_FILE_NAME);
+    let _ = editor.load_history(&history_path);
+    println!("alias-rs shell, `help` for commands, changes are applied on `commit` or `exit`");
+    let flow = loop {
+        let prompt = match session.pending.is_empty() {
+            true => "alias-rs> ",
+            false => "alias-rs*> ",
+        };
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            // Ctrl-C clears the line, Ctrl-D exits
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break Flow::Exit,
+            Err(e) => {
+                return Err(AliasError {
+                    kind: ErrorKind::Unkonw,
+                    msg: format!("read line fail :: {}", e),
+                })
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let _ = editor.add_history_entry(line.as_str());
+        match session.execute(line.trim()) {
+            Ok(Flow::Continue) => {}
+            Ok(flow) => break flow,
+            Err(e) => println!("got some trouble, err={:?}, msg={}", e.kind, e.msg),
+        }
+    };
+    let _ = editor.save_history(&history_path);
+    match flow {
+        Flow::Abort if !session.pending.is_empty() => {
+            println!("aborted :: {} change(s) dropped", session.pending.len())
+        }
+        _ => session.commit()?,
+    }
+    Ok(())
+}
+
+impl Session {
+    fn execute(&mut self, line: &str) -> Result<Flow, AliasError> {
+        let (name, rest) = split_word(line);
+        match name {
+            "help" => println!("{}", HELP),
+            "set" => {
+                let (alias, command) = split_word(rest);
+                let command = unquote(command);
+                if alias.is_empty() || command.is_empty() {
+                    return Err(usage("set <alias> <command>"));
+                }
+                self.stage(format!("set {} {:?}", alias, command), |s| {
+                    s.alias_impl.stage_set(&mut s.tx, alias, command)
+                })?;
+                self.pending
+                    .insert(alias.to_owned(), Some(command.to_owned()));
+            }
+            "remove" => {
+                let alias = single_arg(rest, "remove <alias>")?;
+                self.command(alias)?;
+                self.stage(format!("remove {}", alias), |s| {
+                    s.alias_impl.stage_remove(&mut s.tx, alias)
+                })?;
+                self.pending.insert(alias.to_owned(), None);
+            }
+            "list" => {
+                let setting = self.alias_impl.setting();
+                let aliases = setting.aliases.keys().chain(self.pending.keys());
+                let mut listed = Vec::new();
+                for alias in aliases {
+                    if listed.contains(&alias) {
+                        continue;
+                    }
+                    listed.push(alias);
+                    match self.pending.get(alias) {
+                        Some(Some(_)) => println!("{}    (pending)", alias),
+                        Some(None) => {}
+                        None => println!("{}    ({})", alias, setting.alias_sources[alias]),
+                    }
+                }
+            }
+            "show" => {
+                let alias = single_arg(rest, "show <alias>")?;
+                println!("{}={}", alias, self.command(alias)?);
+            }
+            "edit" => {
+                let alias = single_arg(rest, "edit <alias>")?;
+                match edit_command(&self.tx, self.alias_impl.as_ref(), alias)? {
+                    Some(edited) => {
+                        self.stage(format!("edit {}", alias), |s| {
+                            s.alias_impl.stage_set(&mut s.tx, alias, &edited)
+                        })?;
+                        self.pending.insert(alias.to_owned(), Some(edited));
+                    }
+                    None => println!("nothing changed :: {}", alias),
+                }
+            }
+            "run" => {
+                let (alias, args) = split_word(rest);
+                if alias.is_empty() {
+                    return Err(usage("run <alias> [args...]"));
+                }
+                let command = self.command(alias)?;
+                let args = shlex::split(args).ok_or_else(|| AliasError {
+                    kind: ErrorKind::Unkonw,
+                    msg: format!("unclosed quote in arguments :: {}", args),
+                })?;
+                let code = run_alias(alias, &command, &args)?;
+                if code != 0 {
+                    println!("exit code :: {}", code);
+                }
+            }
+            "commit" => self.commit()?,
+            "discard" => {
+                println!("discarded :: {} change(s)", self.pending.len());
+                self.reset()?;
+            }
+            "exit" | "quit" => return Ok(Flow::Exit),
+            "abort" => return Ok(Flow::Abort),
+            _ => {
+                return Err(AliasError {
+                    kind: ErrorKind::Unkonw,
+                    msg: format!("unknown command, `help` for commands :: {}", name),
+                })
+            }
+        }
+        Ok(Flow::Continue)
+    }
+
+    /// Command of an alias, with the changes not applied yet
+    fn command(&self, alias: &str) -> Result<String, AliasError> {
+        let command = match self.pending.get(alias) {
+            Some(command) => command.clone(),
+            None => self
+                .alias_impl
+                .setting()
+                .aliases
+                .get(alias)
+                .map(|d| d.command().clone()),
+        };
+        command.ok_or_else(|| AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!("alias not found :: {}", alias),
+        })
+    }
+
+    /// Stage a change, nothing is staged if it fails
+    fn stage<F>(&mut self, operation: String, stage: F) -> Result<(), AliasError>
+    where
+        F: FnOnce(&mut Self) -> Result<(), AliasError>,
+    {
+        let staged = self.tx.clone();
+        if let Err(e) = stage(self) {
+            self.tx = staged;
+            return Err(e);
+        }
+        self.operations.push(operation);
+        Ok(())
+    }
+
+    /// Apply the changes, fail if the setting was changed by another process since loaded
+    fn commit(&mut self) -> Result<(), AliasError> {
+        if self.operations.is_empty() {
+            return Ok(());
+        }
+        let _lock = self.alias_impl.lock(self.lock_wait)?;
+        let current = get_alias(&self.setting_path, &self.runtime_variables)?.unwrap();
+        if current.setting().hash != self.alias_impl.setting().hash {
+            return Err(AliasError {
+                kind: ErrorKind::Unkonw,
+                msg: "setting was changed by another process since loaded, `discard` and make the changes again".to_owned(),
+            });
+        }
+        let tx = std::mem::take(&mut self.tx);
+        let count = self.pending.len();
+        self.alias_impl
+            .commit(tx, format!("shell {}", self.operations.join("; ")))?;
+        println!("committed :: {} change(s)", count);
+        refresh_index(&self.setting_path, &self.runtime_variables);
+        self.reset()
+    }
+
+    /// Drop the changes not applied, and reload the setting
+    fn reset(&mut self) -> Result<(), AliasError> {
+        self.alias_impl = get_alias(&self.setting_path, &self.runtime_variables)?.unwrap();
+        self.tx = Transaction::new();
+        self.pending.clear();
+        self.operations.clear();
+        Ok(())
+    }
+}
+
+/// First word of a line and the rest, both trimmed
+fn split_word(line: &str) -> (&str, &str) {
+    match line.trim().split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim()),
+        None => (line.trim(), ""),
+    }
+}
+
+fn single_arg<'a>(rest: &'a str, usage_text: &str) -> Result<&'a str, AliasError> {
+    match split_word(rest) {
+        (arg, "") if !arg.is_empty() => Ok(arg),
+        _ => Err(usage(usage_text)),
+    }
+}
+
+fn usage(usage: &str) -> AliasError {
+    AliasError {
+        kind: ErrorKind::Unkonw,
+        msg: format!("usage :: {}", usage),
+    }
+}
@@ -0,0 +1,156 @@
+use crate::core::{
+    alias::{self, is_valid_alias_name},
+    setting_index::SettingIndex,
+};
+use clap::ValueEnum;
+use std::path::Path;
+
+/// Shells `shell-init` supports
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum InitShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+const BASH_COMPLETE: &str = r#"_alias_rs_complete() {
+    local alias=${COMP_WORDS[0]} wrapped=()
+    _alias_rs_wrapped "$alias"
+    local command=${wrapped[0]}
+    # load the completion of the wrapped command on demand, e.g. by bash-completion
+    complete -p "$command" &>/dev/null || _completion_loader "$command" &>/dev/null
+    local spec completer
+    spec=$(complete -p "$command" 2>/dev/null) || return 0
+    [[ $spec =~ -F\ ([^ ]+) ]] || return 0
+    completer=${BASH_REMATCH[1]}
+    local line=${wrapped[*]}
+    COMP_LINE=$line${COMP_LINE#*"$alias"}
+    COMP_POINT=$((COMP_POINT + ${#line} - ${#alias}))
+    COMP_WORDS=("${wrapped[@]}" "${COMP_WORDS[@]:1}")
+    COMP_CWORD=$((COMP_CWORD + ${#wrapped[@]} - 1))
+    "$completer" "$command" "${COMP_WORDS[COMP_CWORD]}" "${COMP_WORDS[COMP_CWORD-1]}"
+}
+"#;
+
+const ZSH_COMPLETE: &str = r#"_alias_rs_complete() {
+    local -a wrapped
+    _alias_rs_wrapped "$words[1]"
+    words=("${wrapped[@]}" "${(@)words[2,-1]}")
+    (( CURRENT += ${#wrapped} - 1 ))
+    _normal
+}
+"#;
+
+/// Shell code to eval in the shell profile: the script home added to PATH if missing,
+/// and the completion of each alias installed in the script home forwarded to the command it wraps,
+/// with its leading arguments.
+/// Only aliases of a plain command line are forwarded, not of pipes, lists or `$1` arguments.
+/// Names and words are always quoted, as aliases may come from a project setting
+pub fn render(shell: InitShell, index: &SettingIndex) -> String {
+    // (names the alias is installed as, wrapped words)
+    let wrapped: Vec<(Vec<String>, Vec<String>)> = index
+        .aliases
+        .iter()
+        .filter(|(alias, _)| is_valid_alias_name(alias))
+        .filter_map(|(alias, indexed)| {
+            let names = installed_names(&index.script_home, alias);
+            let words = wrapped_words(&indexed.command)?;
+            (!names.is_empty()).then_some((names, words))
+        })
+        .collect();
+    let mut code = String::new();
+    match shell {
+        InitShell::Bash | InitShell::Zsh => {
+            let home = quote(&index.script_home);
+            code.push_str(&format!(
+                "case \":$PATH:\" in\n    *:{}:*) ;;\n    *) export PATH=\"$PATH\":{} ;;\nesac\n",
+                home, home
+            ));
+            if wrapped.is_empty() {
+                return code;
+            }
+            code.push_str("_alias_rs_wrapped() {\n    case \"$1\" in\n");
+            for (names, words) in &wrapped {
+                let names: Vec<String> = names.iter().map(|n| quote(n)).collect();
+                let words: Vec<String> = words.iter().map(|w| quote(w)).collect();
+                code.push_str(&format!(
+                    "        {}) wrapped=({}) ;;\n",
+                    names.join("|"),
+                    words.join(" ")
+                ));
+            }
+            code.push_str("    esac\n}\n");
+            let names: Vec<String> = wrapped
+                .iter()
+                .flat_map(|(names, _)| names.iter().map(|n| quote(n)))
+                .collect();
+            if shell == InitShell::Bash {
+                code.push_str(BASH_COMPLETE);
+                code.push_str(&format!(
+                    "complete -F _alias_rs_complete {}\n",
+                    names.join(" ")
+                ));
+            } else {
+                code.push_str(ZSH_COMPLETE);
+                // compdef is defined by compinit
+                code.push_str(&format!(
+                    "(( $+functions[compdef] )) && compdef _alias_rs_complete {}\n",
+                    names.join(" ")
+                ));
+            }
+        }
+        InitShell::Fish => {
+            let home = quote_fish(&index.script_home);
+            code.push_str(&format!(
+                "contains -- {} $PATH; or set -gx PATH $PATH {}\n",
+                home, home
+            ));
+            for (names, words) in &wrapped {
+                // the wrapped command line is tokenized again by fish
+                let words: Vec<String> = words.iter().map(|w| quote_fish(w)).collect();
+                let words = quote_fish(&words.join(" "));
+                for name in names {
+                    code.push_str(&format!("complete -c {} -w {}\n", quote_fish(name), words));
+                }
+            }
+        }
+    }
+    code
+}
+
+/// Command names an alias is installed as in the script home, e.g. `gco.sh` for a script,
+/// `gco` for a link
+fn installed_names(script_home: &str, alias: &str) -> Vec<String> {
+    alias::installed_names(alias)
+        .into_iter()
+        .filter(|name| Path::new(script_home).join(name).symlink_metadata().is_ok())
+        .collect()
+}
+
+/// Words of a plain command line, `None` if it has shell syntax beyond quoting,
+/// or a word can not be quoted (e.g. with a nul char)
+fn wrapped_words(command: &str) -> Option<Vec<String>> {
+    if command.contains(|c: char| "|&;<>()$`{}\n".contains(c)) {
+        return None;
+    }
+    shlex::split(command).filter(|words| {
+        words.first().is_some_and(|w| !w.is_empty()) && words.iter().all(|w| !w.contains('\0'))
+    })
+}
+
+/// Quote a word for bash and zsh, always quoted, so it is never taken as a pattern or syntax
+fn quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', "'\\''"))
+}
+
+/// Quote a word for fish if needed, where `\` escapes `'` and itself in single quotes
+fn quote_fish(word: &str) -> String {
+    let plain = !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:@+,".contains(c));
+    match plain {
+        true => word.to_owned(),
+        false => format!("'{}'", word.replace('\\', "\\\\").replace('\'', "\\'")),
+    }
+}
@@ -0,0 +1,380 @@
+use super::cmd_parser::{edit_command, load_index, refresh_index};
+use crate::{
+    core::{
+        alias::{self, Alias},
+        alias_search::{self, Field, Query, SearchMode, SearchResult},
+        error::{AliasError, ErrorKind},
+        setting_index::SettingIndex,
+        transaction::{FileState, Transaction},
+        variable::RuntimeVariables,
+    },
+    support::factory::{get_alias, run_alias},
+};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout, Position},
+    style::{Style, Stylize},
+    text::{Line, Span, Text},
+    widgets::{Block, List, ListItem, ListState, Paragraph, Wrap},
+    DefaultTerminal, Frame,
+};
+use std::{
+    io::{self, BufRead},
+    path::Path,
+    time::Duration,
+};
+
+const KEYS_HELP: &str =
+    "type to search  ↑/↓ move  Enter run  ^E edit  ^R rename  ^D remove  Esc quit";
+
+enum Mode {
+    Browse,
+    /// new name typed for the selected alias
+    Rename(String),
+    /// waiting for `y` to remove the selected alias
+    Remove,
+}
+
+/// What to do after a key
+enum Action {
+    None,
+    Run,
+    Edit,
+    Quit,
+}
+
+/// Full-screen alias browser with fuzzy search and a preview of the generated script
+struct Tui {
+    setting_path: Option<String>,
+    runtime_variables: RuntimeVariables,
+    lock_wait: Option<Duration>,
+    index: SettingIndex,
+    query: String,
+    results: Vec<SearchResult>,
+    list_state: ListState,
+    mode: Mode,
+    /// result of the last action, shown in place of the key help
+    message: Option<String>,
+}
+
+pub fn start(
+    setting_path: &Option<String>,
+    runtime_variables: &RuntimeVariables,
+    lock_wait: Option<Duration>,
+) -> Result<(), AliasError> {
+    let mut tui = Tui {
+        setting_path: setting_path.clone(),
+        runtime_variables: runtime_variables.clone(),
+        lock_wait,
+        index: load_index(setting_path, runtime_variables)?,
+        query: String::new(),
+        results: Vec::new(),
+        list_state: ListState::default(),
+        mode: Mode::Browse,
+        message: None,
+    };
+    tui.search();
+    let mut terminal = ratatui::try_init().map_err(terminal_error)?;
+    let result = tui.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+fn terminal_error(e: io::Error) -> AliasError {
+    AliasError {
+        kind: ErrorKind::Unkonw,
+        msg: format!("terminal fail :: {}", e),
+    }
+}
+
+impl Tui {
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<(), AliasError> {
+        loop {
+            terminal
+                .draw(|frame| self.draw(frame))
+                .map_err(terminal_error)?;
+            let Event::Key(key) = event::read().map_err(terminal_error)? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            let result = match self.handle_key(key) {
+                Ok(Action::None) => Ok(()),
+                Ok(Action::Quit) => return Ok(()),
+                // these leave the full screen for the command or the editor
+                Ok(Action::Run) => suspend(terminal, || self.run_selected()),
+                Ok(Action::Edit) => suspend(terminal, || self.edit_selected()),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                self.message = Some(e.msg);
+            }
+        }
+    }
+
+    fn selected(&self) -> Option<&SearchResult> {
+        self.results.get(self.list_state.selected()?)
+    }
+
+    fn selected_alias(&self) -> Result<String, AliasError> {
+        self.selected()
+            .map(|r| r.alias.clone())
+            .ok_or_else(|| AliasError {
+                kind: ErrorKind::Unkonw,
+                msg: "no alias selected".to_owned(),
+            })
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Result<Action, AliasError> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match &mut self.mode {
+            Mode::Rename(target) => match key.code {
+                KeyCode::Esc => self.mode = Mode::Browse,
+                KeyCode::Backspace => {
+                    target.pop();
+                }
+                KeyCode::Char(c) if !ctrl => target.push(c),
+                KeyCode::Enter => {
+                    let target = target.clone();
+                    self.mode = Mode::Browse;
+                    let source = self.selected_alias()?;
+                    self.change(format!("rename {} {}", source, target), |alias_impl, tx| {
+                        alias_impl.stage_copy(tx, &source, &target, true)
+                    })?;
+                    self.message = Some(format!("renamed :: {} -> {}", source, target));
+                }
+                _ => {}
+            },
+            Mode::Remove => {
+                self.mode = Mode::Browse;
+                if key.code == KeyCode::Char('y') {
+                    let alias = self.selected_alias()?;
+                    self.change(format!("remove {}", alias), |alias_impl, tx| {
+                        alias_impl.stage_remove(tx, &alias)
+                    })?;
+                    self.message = Some(format!("removed :: {}", alias));
+                }
+            }
+            Mode::Browse => match key.code {
+                KeyCode::Esc => return Ok(Action::Quit),
+                KeyCode::Char('c') if ctrl => return Ok(Action::Quit),
+                KeyCode::Up => self.list_state.select_previous(),
+                KeyCode::Char('p') if ctrl => self.list_state.select_previous(),
+                KeyCode::Down => self.list_state.select_next(),
+                KeyCode::Char('n') if ctrl => self.list_state.select_next(),
+                KeyCode::Enter => return Ok(Action::Run),
+                KeyCode::Char('e') if ctrl => return Ok(Action::Edit),
+                KeyCode::Char('r') if ctrl => {
+                    self.selected_alias()?;
+                    self.mode = Mode::Rename(String::new());
+                }
+                KeyCode::Char('d') if ctrl => {
+                    self.selected_alias()?;
+                    self.mode = Mode::Remove;
+                }
+                KeyCode::Backspace => {
+                    self.query.pop();
+                    self.search();
+                }
+                KeyCode::Char(c) if !ctrl => {
+                    self.query.push(c);
+                    self.search();
+                }
+                _ => {}
+            },
+        }
+        Ok(Action::None)
+    }
+
+    fn search(&mut self) {
+        // a fuzzy query always compiles
+        self.results = Query::new(&self.query, SearchMode::Fuzzy)
+            .map(|query| alias_search::search(&self.index, &query))
+            .unwrap_or_default();
+        let selected = match self.results.is_empty() {
+            true => None,
+            false => Some(self.list_state.selected().unwrap_or(0)),
+        };
+        self.list_state
+            .select(selected.map(|i| i.min(self.results.len().saturating_sub(1))));
+    }
+
+    /// Change aliases the same as the CLI does, in a transaction recorded in the journal
+    fn change<F>(&mut self, operation: String, stage: F) -> Result<(), AliasError>
+    where
+        F: FnOnce(&dyn Alias, &mut Transaction) -> Result<(), AliasError>,
+    {
+        let alias_impl = get_alias(&self.setting_path, &self.runtime_variables)?.unwrap();
+        let _lock = alias_impl.lock(self.lock_wait)?;
+        let mut tx = Transaction::new();
+        stage(alias_impl.as_ref(), &mut tx)?;
+        alias_impl.commit(tx, operation)?;
+        refresh_index(&self.setting_path, &self.runtime_variables);
+        self.index = load_index(&self.setting_path, &self.runtime_variables)?;
+        self.search();
+        Ok(())
+    }
+
+    fn run_selected(&mut self) -> Result<(), AliasError> {
+        let alias = self.selected_alias()?;
+        let command = self.index.aliases[&alias].command.clone();
+        println!("$ {}", command);
+        let code = run_alias(&alias, &command, &[])?;
+        println!("\nexit code :: {}, press Enter to return", code);
+        let _ = io::stdin().lock().read_line(&mut String::new());
+        self.message = Some(format!("ran :: {} :: exit code {}", alias, code));
+        Ok(())
+    }
+
+    fn edit_selected(&mut self) -> Result<(), AliasError> {
+        let alias = self.selected_alias()?;
+        let alias_impl = get_alias(&self.setting_path, &self.runtime_variables)?.unwrap();
+        let Some(edited) = edit_command(&Transaction::new(), alias_impl.as_ref(), &alias)? else {
+            self.message = Some(format!("nothing changed :: {}", alias));
+            return Ok(());
+        };
+        self.change(format!("edit {}", alias), |alias_impl, tx| {
+            alias_impl.stage_set(tx, &alias, &edited)
+        })?;
+        self.message = Some(format!("edited :: {}", alias));
+        Ok(())
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [search_area, main_area, status_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [list_area, preview_area] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .areas(main_area);
+
+        let (title, input) = match &self.mode {
+            Mode::Rename(target) => ("rename to", target.as_str()),
+            _ => ("search", self.query.as_str()),
+        };
+        let search = Paragraph::new(input).block(Block::bordered().title(title));
+        frame.render_widget(search, search_area);
+        frame.set_cursor_position(Position::new(
+            search_area.x + 1 + input.chars().count() as u16,
+            search_area.y + 1,
+        ));
+
+        let items: Vec<ListItem> = self
+            .results
+            .iter()
+            .map(|result| {
+                let mut spans = highlight(&result.alias, result.indices(Field::Name));
+                let command = &self.index.aliases[&result.alias].command;
+                spans.push(Span::raw("  "));
+                spans.extend(
+                    highlight(command, result.indices(Field::Command))
+                        .into_iter()
+                        .map(|span| span.dark_gray()),
+                );
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+        let title = format!(
+            "aliases {}/{}",
+            self.results.len(),
+            self.index.aliases.len()
+        );
+        let list = List::new(items)
+            .block(Block::bordered().title(title))
+            .highlight_style(Style::new().reversed());
+        frame.render_stateful_widget(list, list_area, &mut self.list_state);
+
+        let preview = Paragraph::new(self.preview())
+            .block(Block::bordered().title("preview"))
+            .wrap(Wrap { trim: false });
+        frame.render_widget(preview, preview_area);
+
+        let status = match (&self.mode, &self.message) {
+            (Mode::Remove, _) => format!(
+                "remove {}? y to confirm, any other key to cancel",
+                self.selected().map_or("", |r| r.alias.as_str())
+            ),
+            (Mode::Rename(_), _) => "Enter rename  Esc cancel".to_owned(),
+            (Mode::Browse, Some(message)) => message.clone(),
+            (Mode::Browse, None) => KEYS_HELP.to_owned(),
+        };
+        frame.render_widget(Paragraph::new(status), status_area);
+    }
+
+    /// Define of the selected alias and its generated script, or the link to the binary
+    fn preview(&self) -> Text<'static> {
+        let Some(result) = self.selected() else {
+            return Text::raw("no alias found");
+        };
+        let indexed = &self.index.aliases[&result.alias];
+        let mut lines = vec![Line::from(vec![
+            Span::raw("command: ").bold(),
+            Span::raw(indexed.command.clone()),
+        ])];
+        if let Some(description) = &indexed.description {
+            lines.push(Line::from(vec![
+                Span::raw("description: ").bold(),
+                Span::raw(description.clone()),
+            ]));
+        }
+        if !indexed.tags.is_empty() {
+            lines.push(Line::from(vec![
+                Span::raw("tags: ").bold(),
+                Span::raw(indexed.tags.join(", ")),
+            ]));
+        }
+        lines.push(Line::from(vec![
+            Span::raw("source: ").bold(),
+            Span::raw(indexed.source.clone()),
+        ]));
+        lines.push(Line::default());
+        match script_of(&self.index.script_home, &result.alias) {
+            Some((name, FileState::Content(content))) => {
+                lines.push(Line::from(name).bold());
+                let content = String::from_utf8_lossy(&content).to_string();
+                lines.extend(content.lines().map(|l| Line::raw(l.to_owned())));
+            }
+            Some((name, FileState::Link(target))) => {
+                lines.push(Line::from(format!("{} -> {}", name, target)).bold());
+            }
+            None => lines.push(Line::from("no script generated, run `alias-rs import`").italic()),
+        }
+        Text::from(lines)
+    }
+}
+
+/// Leave the full screen to run `f`, then come back
+fn suspend<F>(terminal: &mut DefaultTerminal, f: F) -> Result<(), AliasError>
+where
+    F: FnOnce() -> Result<(), AliasError>,
+{
+    ratatui::restore();
+    let result = f();
+    *terminal = ratatui::try_init().map_err(terminal_error)?;
+    terminal.clear().map_err(terminal_error)?;
+    result
+}
+
+/// Script (or link) of an alias in the script home, with its file name
+fn script_of(script_home: &str, alias: &str) -> Option<(String, FileState)> {
+    alias::installed_names(alias).into_iter().find_map(|name| {
+        let path = Path::new(script_home).join(&name);
+        let state = FileState::read(&path.to_string_lossy()).ok()??;
+        Some((name, state))
+    })
+}
+
+/// Spans of a text with the chars at `indices` highlighted
+fn highlight(text: &str, indices: &[usize]) -> Vec<Span<'static>> {
+    text.chars()
+        .enumerate()
+        .map(|(i, c)| match indices.contains(&i) {
+            true => Span::raw(c.to_string()).yellow().bold(),
+            false => Span::raw(c.to_string()),
+        })
+        .collect()
+}
@@ -0,0 +1,44 @@
+use crate::core::error::{AliasError, ErrorKind};
+use std::{
+    env::{self, consts::OS},
+    process::Command,
+};
+
+/// Editor command from `$VISUAL` or `$EDITOR`, `vi` (`notepad` on Windows) if neither is set
+pub fn get_editor() -> String {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| match OS {
+            "windows" => "notepad".to_owned(),
+            _ => "vi".to_owned(),
+        })
+}
+
+/// Open a file in the editor and wait for it, fail if the editor exits with non-zero.
+/// The editor may have arguments, e.g. `code --wait`
+pub fn open(path: &str) -> Result<(), AliasError> {
+    let editor = get_editor();
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or_default();
+    let status = Command::new(program)
+        .args(words)
+        .arg(path)
+        .status()
+        .map_err(|e| AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!("open editor fail :: {} :: {}", editor, e),
+        })?;
+    if !status.success() {
+        return Err(AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!(
+                "editor exited with non-zero, nothing changed :: {} :: exit_code={}",
+                editor,
+                status.code().unwrap_or(-999)
+            ),
+        });
+    }
+    Ok(())
+}
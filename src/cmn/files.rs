@@ -1,43 +1,69 @@
 use std::{
+    env::{self, consts::OS},
     fs::{self, File},
-    path::Path,
+    path::{Path, PathBuf},
 };
 
-pub fn create_new_with_all_dir(path: &String) -> Result<File, std::io::Error> {
-    if let Some(parent) = Path::new(path).parent() {
-        fs::create_dir_all(parent)?;
-    }
-    File::create_new(path)
+/// Create a symbolic link at `link` pointing to the file `target`
+pub fn symlink<P: AsRef<Path>>(target: &str, link: P) -> Result<(), std::io::Error> {
+    #[cfg(windows)]
+    return std::os::windows::fs::symlink_file(target, link);
+    #[cfg(not(windows))]
+    return std::os::unix::fs::symlink(target, link);
 }
 
-pub fn create_with_all_dir(path: &String) -> Result<File, std::io::Error> {
+pub fn create_new_with_all_dir(path: &String) -> Result<File, std::io::Error> {
     if let Some(parent) = Path::new(path).parent() {
         fs::create_dir_all(parent)?;
     }
-    File::create(path)
-}
-
-pub fn remove_if_present(path: &String) -> Result<(), std::io::Error> {
-    if Path::new(path).exists() {
-        return fs::remove_file(path);
-    }
-    Ok(())
+    File::create_new(path)
 }
 
+/// File and link names in a directory, sorted, hidden files (e.g. lock files) are skipped
 pub fn list_dir(path: &String) -> Result<Option<Vec<String>>, std::io::Error> {
     let path = Path::new(path);
     if !path.is_dir() {
         return Ok(None);
     }
     let mut list = Vec::new();
-    for entry in fs::read_dir(path)? {
-        if let Ok(entry) = entry {
-            if let Ok(file_type) = entry.file_type() {
-                if file_type.is_file() {
-                    list.push(entry.file_name().to_string_lossy().to_string());
-                }
+    for entry in fs::read_dir(path)?.flatten() {
+        if let Ok(file_type) = entry.file_type() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if (file_type.is_file() || file_type.is_symlink()) && !name.starts_with('.') {
+                list.push(name);
             }
         }
     }
-    return Ok(Some(list));
+    list.sort();
+    Ok(Some(list))
+}
+
+/// Executable file of a command name searched in `PATH` (`.exe` on Windows), or of an absolute path,
+/// directories `skip` returns true for are not searched
+pub fn which(name: &str, skip: impl Fn(&Path) -> bool) -> Option<PathBuf> {
+    let file_name = match OS {
+        "windows" if Path::new(name).extension().is_none() => format!("{}.exe", name),
+        _ => name.to_owned(),
+    };
+    let path = Path::new(&file_name);
+    if path.is_absolute() {
+        return is_executable(path).then(|| path.to_path_buf());
+    }
+    if path.components().count() > 1 {
+        return None;
+    }
+    env::split_paths(&env::var_os("PATH")?)
+        .filter(|dir| dir.is_absolute() && !skip(dir))
+        .map(|dir| dir.join(&file_name))
+        .find(|path| is_executable(path))
+}
+
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+    }
+    #[cfg(not(unix))]
+    path.is_file()
 }
@@ -1,3 +1,5 @@
+pub mod editor;
 pub mod files;
+pub mod strings;
 pub mod unix_like;
 pub mod windows_like;
@@ -0,0 +1,27 @@
+/// Levenshtein distance of two strings, counted in chars
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            current[j + 1] = (previous[j] + cost)
+                .min(previous[j + 1] + 1)
+                .min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The candidate most similar to `target`, if it is similar enough
+pub fn most_similar<'a>(target: &str, candidates: &'a [String]) -> Option<&'a String> {
+    let max_distance = (target.chars().count() / 3).max(2);
+    candidates
+        .iter()
+        .map(|c| (edit_distance(target, c), c))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, c)| c)
+}
@@ -45,10 +45,10 @@ pub fn get_shell_profile() -> Result<(String, File), AliasError> {
                     }),
                 };
             }
-            return Err(AliasError {
+            Err(AliasError {
                 kind: ErrorKind::Unkonw,
                 msg: format!("shell profile not exists :: {}", shell_profile_path),
-            });
+            })
         }
         Shell::Unsupported(shell_name) => Err(AliasError {
             kind: ErrorKind::Unkonw,
@@ -1,25 +1,17 @@
 use crate::core::error::{AliasError, ErrorKind};
 use encoding_rs::GBK;
-use std::{
-    io::Write,
-    process::{Command, ExitStatus},
-};
-
-use super::files;
+use std::process::{Command, ExitStatus};
 
 pub fn get_local_app_home() -> String {
     std::env::var("LocalAppData").map_or(String::default(), |val| val)
 }
 
-pub fn create_ansi_file(path: &String, content: &String) -> Result<(), AliasError> {
-    let encoded_str = GBK.encode(&content).0;
-    files::create_with_all_dir(path)
-        .and_then(|mut f| f.write_all(&encoded_str))
-        .map_err(|e| AliasError {
-            kind: ErrorKind::Unkonw,
-            msg: format!(" create ansi file fail :: {}", e),
-        })?;
-    Ok(())
+pub fn get_program_data_home() -> String {
+    std::env::var("ProgramData").map_or(String::default(), |val| val)
+}
+
+pub fn encode_ansi(content: &str) -> Vec<u8> {
+    GBK.encode(content).0.into_owned()
 }
 
 pub struct ExecuteCmdResult {
@@ -109,7 +101,7 @@ pub fn get_user_env_var(var_name: &String) -> Result<Option<String>, AliasError>
         if let Some(stdout_vec) = result.get_stdout_vec() {
             Ok(Some(
                 stdout_vec
-                    .get(0)
+                    .first()
                     .unwrap()
                     .splitn(4, "    ")
                     .last()
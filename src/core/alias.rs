@@ -1,9 +1,280 @@
-use super::{alias_setting::AliasSetting, error::AliasError};
+use super::{
+    alias_history,
+    alias_setting::{self, AliasKind, AliasSetting},
+    backup::EnvSnapshot,
+    error::{AliasError, ErrorKind},
+    journal::Journal,
+    lock::DirLock,
+    transaction::Transaction,
+};
+use crate::cmn::files;
+use std::{env::consts::OS, path::Path, time::Duration};
 
 pub trait Alias {
-    fn init(&self) -> Result<(), AliasError>;
+    /// stage the system env setup, e.g. adding the script home to PATH in the shell profile
+    fn stage_init(&self, tx: &mut Transaction) -> Result<(), AliasError>;
+    /// the system env set up by `init`, for backup
+    fn snapshot_env(&self) -> Result<EnvSnapshot, AliasError>;
+    /// stage restoring the system env from a backup
+    fn stage_env_restore(
+        &self,
+        tx: &mut Transaction,
+        snapshot: &EnvSnapshot,
+    ) -> Result<(), AliasError>;
     fn setting(&self) -> AliasSetting;
-    fn set(&self, alias: String, command: String) -> Result<(), AliasError>;
-    fn remove(&self, alias: String) -> Result<(), AliasError>;
+    /// user setting path, where `set` and `remove` write aliases
+    fn setting_path(&self) -> String;
+    /// stage writing the alias script, or the link to the executable the command names
+    fn stage_script(
+        &self,
+        tx: &mut Transaction,
+        alias: &str,
+        command: &str,
+        kind: AliasKind,
+    ) -> Result<(), AliasError>;
+    /// stage removing the alias script
+    fn stage_script_removal(&self, tx: &mut Transaction, alias: &str) -> Result<(), AliasError>;
     fn list(&self) -> Result<Option<Vec<String>>, AliasError>;
+    /// lock the setting dir and the script home against other alias-rs processes,
+    /// held until the returned locks are dropped
+    fn lock(&self, wait: Option<Duration>) -> Result<Vec<DirLock>, AliasError>;
+
+    /// journal of the operations on the user setting, beside it
+    fn journal(&self) -> Journal {
+        Journal::new(&alias_setting::get_setting_dir(&self.setting_path()))
+    }
+
+    /// commit a transaction and record it in the journal
+    fn commit(&self, tx: Transaction, operation: String) -> Result<(), AliasError> {
+        let changes = tx.commit()?;
+        self.journal().append(operation, Vec::new(), changes)?;
+        Ok(())
+    }
+
+    /// stage setting an alias, both its script and its entry in the user setting
+    fn stage_set(
+        &self,
+        tx: &mut Transaction,
+        alias: &str,
+        command: &str,
+    ) -> Result<(), AliasError> {
+        check_alias_name(alias)?;
+        if command.trim().is_empty() {
+            return Err(AliasError {
+                kind: ErrorKind::Unkonw,
+                msg: format!("alias command should not be empty :: {}", alias),
+            });
+        }
+        let setting = self.setting();
+        let kind = setting
+            .aliases
+            .get(alias)
+            .map_or(AliasKind::default(), |d| d.kind());
+        self.stage_script(tx, alias, command, kind)?;
+        alias_setting::stage_alias_command(tx, &self.setting_path(), alias, command)?;
+        self.stage_version(tx, alias, Some(command))
+    }
+
+    /// stage removing an alias, both its script and its entry in the user setting
+    fn stage_remove(&self, tx: &mut Transaction, alias: &str) -> Result<(), AliasError> {
+        self.stage_script_removal(tx, alias)?;
+        alias_setting::stage_alias_removal(tx, &self.setting_path(), alias)?;
+        self.stage_version(tx, alias, None)
+    }
+
+    /// stage copying an alias to a new name, its script, setting entry and metadata,
+    /// the source is removed if `rename`
+    fn stage_copy(
+        &self,
+        tx: &mut Transaction,
+        source: &str,
+        target: &str,
+        rename: bool,
+    ) -> Result<(), AliasError> {
+        check_alias_name(target)?;
+        let setting = self.setting();
+        let Some(define) = setting.aliases.get(source) else {
+            return Err(AliasError {
+                kind: ErrorKind::Unkonw,
+                msg: format!("alias not found :: {}", source),
+            });
+        };
+        let scripts = self.list()?.unwrap_or_default();
+        let script_exists = installed_names(target)
+            .iter()
+            .any(|name| scripts.contains(name));
+        if setting.aliases.contains_key(target) || script_exists {
+            return Err(AliasError {
+                kind: ErrorKind::Unkonw,
+                msg: format!(
+                    "alias already exists, remove it first :: {}{}",
+                    target,
+                    setting
+                        .alias_sources
+                        .get(target)
+                        .map_or(String::default(), |s| format!(" :: {}", s))
+                ),
+            });
+        }
+        let setting_path = self.setting_path();
+        let setting_dir = alias_setting::get_setting_dir(&setting_path);
+        alias_setting::stage_alias_copy(tx, &setting_path, source, target, define, rename)?;
+        self.stage_script(tx, target, define.command(), define.kind())?;
+        if rename {
+            self.stage_script_removal(tx, source)?;
+            alias_history::stage_move(tx, &setting_dir, source, target)
+        } else {
+            self.stage_version(tx, target, Some(define.command()))
+        }
+    }
+
+    /// stage replacing the user setting, the scripts of the aliases changed by it are regenerated,
+    /// `new_setting` is the setting loaded with the new content
+    fn stage_setting_replace(
+        &self,
+        tx: &mut Transaction,
+        content: Vec<u8>,
+        new_setting: &AliasSetting,
+    ) -> Result<(), AliasError> {
+        tx.write(self.setting_path(), content);
+        let setting = self.setting();
+        for (alias, define) in &new_setting.aliases {
+            let current = setting.aliases.get(alias);
+            if current.map(|d| d.command()) != Some(define.command()) {
+                self.stage_script(tx, alias, define.command(), define.kind())?;
+                self.stage_version(tx, alias, Some(define.command()))?;
+            } else if current.map(|d| d.kind()) != Some(define.kind()) {
+                self.stage_script(tx, alias, define.command(), define.kind())?;
+            }
+        }
+        for alias in setting.aliases.keys() {
+            if !new_setting.aliases.contains_key(alias) {
+                self.stage_script_removal(tx, alias)?;
+                self.stage_version(tx, alias, None)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// stage recording the new command version of an alias, `None` if it is removed
+    fn stage_version(
+        &self,
+        tx: &mut Transaction,
+        alias: &str,
+        command: Option<&str>,
+    ) -> Result<(), AliasError> {
+        // record the commands as written, with their placeholders, the one before the change
+        // from the setting on disk and the new one as staged
+        let setting = self.setting();
+        let setting_path = self.setting_path();
+        let current =
+            alias_setting::raw_alias_command(&Transaction::new(), &setting, &setting_path, alias)?;
+        let command = match command {
+            Some(command) => Some(
+                alias_setting::raw_alias_command(tx, &setting, &setting_path, alias)?
+                    .unwrap_or_else(|| command.to_owned()),
+            ),
+            None => None,
+        };
+        let setting_dir = alias_setting::get_setting_dir(&setting_path);
+        alias_history::stage_record(
+            tx,
+            &setting_dir,
+            alias,
+            current.as_deref(),
+            command.as_deref(),
+        )
+    }
+}
+
+/// Executable an alias of `kind` links to, `None` if it should be a script.
+/// Only a single word command without shell syntax can be linked, executables in the script home
+/// are skipped, so an alias never links to another alias
+pub fn link_target(
+    alias: &str,
+    command: &str,
+    kind: AliasKind,
+    script_home: &str,
+) -> Result<Option<String>, AliasError> {
+    if kind == AliasKind::Script {
+        return Ok(None);
+    }
+    let command = command.trim();
+    let single =
+        !command.is_empty() && !command.contains(char::is_whitespace) && !has_shell_syntax(command);
+    let script_home = Path::new(script_home);
+    let target = single
+        .then(|| files::which(command, |dir| dir == script_home))
+        .flatten();
+    match (kind, target) {
+        (_, Some(target)) => Ok(Some(target.to_string_lossy().to_string())),
+        (AliasKind::Link, None) => Err(AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!(
+                "link alias command should be a single executable found in PATH :: {} :: {}",
+                alias, command
+            ),
+        }),
+        _ => Ok(None),
+    }
+}
+
+/// Whether a command needs a shell, for quoting, expansions, redirections, etc.,
+/// a command without it is only words split by whitespace
+pub fn has_shell_syntax(command: &str) -> bool {
+    command.contains(|c: char| "$`'\"\\|&;<>(){}[]*?~#=!%".contains(c))
+}
+
+/// File names an alias may be installed as in the script home, its script and its link,
+/// e.g. `gs.sh` and `gs`. Matched exactly, as an alias name may contain '.'
+pub fn installed_names(alias: &str) -> [String; 2] {
+    match OS {
+        "windows" => [format!("{}.bat", alias), format!("{}.exe", alias)],
+        _ => [format!("{}.sh", alias), alias.to_owned()],
+    }
+}
+
+/// Alias is used as the script file name and the command name,
+/// so only letters, digits, '_', '-' and '.' are allowed, and it can not start with '-' or '.'
+pub fn is_valid_alias_name(alias: &str) -> bool {
+    let mut chars = alias.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphanumeric() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+}
+
+/// Fail with the allowed characters if an alias name is not valid, see [`is_valid_alias_name`]
+pub fn check_alias_name(alias: &str) -> Result<(), AliasError> {
+    if is_valid_alias_name(alias) {
+        return Ok(());
+    }
+    Err(AliasError {
+        kind: ErrorKind::Unkonw,
+        msg: format!(
+            "invalid alias name, use only letters, digits, '_', '-' and '.' :: {}",
+            alias
+        ),
+    })
+}
+
+/// Replace the characters not allowed in an alias name with '_'
+pub fn to_valid_alias_name(alias: &str) -> String {
+    let name: String = alias
+        .trim_start_matches(['-', '.'])
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if name.is_empty() {
+        "_".to_owned()
+    } else {
+        name
+    }
 }
@@ -0,0 +1,132 @@
+use super::{
+    error::{AliasError, ErrorKind},
+    transaction::Transaction,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const HISTORY_DIR_NAME: &str = "history";
+
+/// A command version of an alias
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AliasVersion {
+    /// 1-based, increased by every change
+    pub version: u32,
+    /// seconds since unix epoch
+    pub time: u64,
+    /// `None` if the alias was removed
+    pub command: Option<String>,
+}
+
+/// Directory of the alias versions, in the setting dir
+pub fn history_dir(setting_dir: &str) -> String {
+    Path::new(setting_dir)
+        .join(HISTORY_DIR_NAME)
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Path of the versions of an alias, one JSON version per line
+fn history_path(setting_dir: &str, alias: &str) -> String {
+    Path::new(&history_dir(setting_dir))
+        .join(format!("{}.jsonl", alias))
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Versions of an alias as the transaction would leave them, oldest first
+pub fn versions(
+    tx: &Transaction,
+    setting_dir: &str,
+    alias: &str,
+) -> Result<Vec<AliasVersion>, AliasError> {
+    let path = history_path(setting_dir, alias);
+    let content = match tx.read(&path)? {
+        Some(content) => String::from_utf8_lossy(&content).to_string(),
+        None => return Ok(Vec::new()),
+    };
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).map_err(|e| AliasError {
+                kind: ErrorKind::Unkonw,
+                msg: format!(
+                    "parse alias history fail :: {}:{} :: {}",
+                    path,
+                    index + 1,
+                    e
+                ),
+            })
+        })
+        .collect()
+}
+
+/// Stage recording a new version of an alias, nothing is recorded if the command is unchanged.
+/// `current` is the command before the change, recorded first if the alias has no history yet
+pub fn stage_record(
+    tx: &mut Transaction,
+    setting_dir: &str,
+    alias: &str,
+    current: Option<&str>,
+    command: Option<&str>,
+) -> Result<(), AliasError> {
+    if current.is_some() && versions(tx, setting_dir, alias)?.is_empty() {
+        stage_append(tx, setting_dir, alias, None, current)?;
+    }
+    stage_append(
+        tx,
+        setting_dir,
+        alias,
+        versions(tx, setting_dir, alias)?.last(),
+        command,
+    )
+}
+
+fn stage_append(
+    tx: &mut Transaction,
+    setting_dir: &str,
+    alias: &str,
+    last: Option<&AliasVersion>,
+    command: Option<&str>,
+) -> Result<(), AliasError> {
+    if last.map_or(command.is_none(), |v| v.command.as_deref() == command) {
+        return Ok(());
+    }
+    let version = AliasVersion {
+        version: last.map_or(1, |v| v.version + 1),
+        time: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+        command: command.map(|c| c.to_owned()),
+    };
+    let line = serde_json::to_string(&version).map_err(|e| AliasError {
+        kind: ErrorKind::Unkonw,
+        msg: format!("serialize alias version fail :: {}", e),
+    })?;
+    let path = history_path(setting_dir, alias);
+    let mut content = tx.read(&path)?.unwrap_or_default();
+    content.extend_from_slice(line.as_bytes());
+    content.push(b'\n');
+    tx.write(path, content);
+    Ok(())
+}
+
+/// Stage moving the versions of an alias to a new name
+pub fn stage_move(
+    tx: &mut Transaction,
+    setting_dir: &str,
+    source: &str,
+    target: &str,
+) -> Result<(), AliasError> {
+    let source_path = history_path(setting_dir, source);
+    if let Some(content) = tx.read(&source_path)? {
+        tx.write(history_path(setting_dir, target), content);
+        tx.remove(source_path);
+    }
+    Ok(())
+}
@@ -1,8 +1,12 @@
-use crate::core::{alias::Alias, error::AliasError};
-use std::rc::Rc;
+use crate::core::{
+    alias::{check_alias_name, Alias},
+    error::AliasError,
+    transaction::Transaction,
+};
+use std::{rc::Rc, time::Duration};
 
 pub trait AliasImport {
-    fn import(&self) -> Result<(), AliasError>;
+    fn import(&self, lock_wait: Option<Duration>) -> Result<(), AliasError>;
 }
 
 pub struct AliasImporter {
@@ -18,10 +22,16 @@ impl AliasImporter {
 }
 
 impl AliasImport for AliasImporter {
-    fn import(&self) -> Result<(), AliasError> {
-        for (alias, command) in &self.alias.setting().aliases {
-            self.alias.set(alias.clone(), command.clone())?;
+    /// Write the scripts of all setting aliases in one transaction,
+    /// nothing is written if any of them fails
+    fn import(&self, lock_wait: Option<Duration>) -> Result<(), AliasError> {
+        let _lock = self.alias.lock(lock_wait)?;
+        let mut tx = Transaction::new();
+        for (alias, define) in &self.alias.setting().aliases {
+            check_alias_name(alias)?;
+            self.alias
+                .stage_script(&mut tx, alias, define.command(), define.kind())?;
         }
-        Ok(())
+        self.alias.commit(tx, "import".to_owned())
     }
 }
@@ -0,0 +1,150 @@
+use super::{
+    error::{AliasError, ErrorKind},
+    setting_index::{IndexedAlias, SettingIndex},
+};
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use regex::Regex;
+use std::cmp::Reverse;
+
+/// Field of an alias searched
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Name,
+    Command,
+    Description,
+    Tags,
+}
+
+impl Field {
+    /// a match in the name counts more than in the other fields
+    fn weight(&self) -> i64 {
+        match self {
+            Field::Name => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// How the query matches a field
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchMode {
+    /// chars of the query in order, not necessarily adjacent, ignoring case
+    Fuzzy,
+    /// the query as a substring, ignoring case
+    Exact,
+    /// the query as a regular expression
+    Regex,
+}
+
+/// A query compiled for its mode
+pub enum Query {
+    Fuzzy(Box<SkimMatcherV2>, String),
+    Pattern(Regex),
+}
+
+impl Query {
+    pub fn new(query: &str, mode: SearchMode) -> Result<Self, AliasError> {
+        let pattern = match mode {
+            SearchMode::Fuzzy => {
+                let matcher = Box::new(SkimMatcherV2::default().ignore_case());
+                return Ok(Query::Fuzzy(matcher, query.to_owned()));
+            }
+            SearchMode::Exact => format!("(?i){}", regex::escape(query)),
+            SearchMode::Regex => query.to_owned(),
+        };
+        Regex::new(&pattern)
+            .map(Query::Pattern)
+            .map_err(|e| AliasError {
+                kind: ErrorKind::Unkonw,
+                msg: format!("parse search pattern fail :: {} :: {}", query, e),
+            })
+    }
+
+    /// Score and indices of the matched chars in a text, `None` if not matched.
+    /// Only a fuzzy match is scored, otherwise aliases are ranked by the field matched
+    fn find(&self, text: &str) -> Option<(i64, Vec<usize>)> {
+        match self {
+            Query::Fuzzy(_, query) if query.is_empty() => Some((0, Vec::new())),
+            Query::Fuzzy(matcher, query) => matcher.fuzzy_indices(text, query),
+            Query::Pattern(regex) => {
+                let ranges: Vec<_> = regex.find_iter(text).map(|m| m.range()).collect();
+                if ranges.is_empty() {
+                    return None;
+                }
+                let indices = text
+                    .char_indices()
+                    .enumerate()
+                    .filter(|(_, (byte, _))| ranges.iter().any(|r| r.contains(byte)))
+                    .map(|(i, _)| i)
+                    .collect();
+                Some((1, indices))
+            }
+        }
+    }
+}
+
+/// A field matched, with the indices of the matched chars in its text
+#[derive(Debug, Clone)]
+pub struct FieldMatch {
+    pub field: Field,
+    pub indices: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub alias: String,
+    pub score: i64,
+    pub matches: Vec<FieldMatch>,
+}
+
+impl SearchResult {
+    /// Indices of the matched chars in a field, empty if not matched
+    pub fn indices(&self, field: Field) -> &[usize] {
+        self.matches
+            .iter()
+            .find(|m| m.field == field)
+            .map_or(&[], |m| m.indices.as_slice())
+    }
+}
+
+/// Texts of the searched fields of an alias, tags are joined with ", "
+pub fn fields(alias: &str, indexed: &IndexedAlias) -> Vec<(Field, String)> {
+    let mut fields = vec![
+        (Field::Name, alias.to_owned()),
+        (Field::Command, indexed.command.clone()),
+    ];
+    if let Some(description) = &indexed.description {
+        fields.push((Field::Description, description.clone()));
+    }
+    if !indexed.tags.is_empty() {
+        fields.push((Field::Tags, indexed.tags.join(", ")));
+    }
+    fields
+}
+
+/// Aliases matching the query in any field, best first, all aliases in setting order
+/// if a fuzzy query is empty. An alias scores its best weighted field
+pub fn search(index: &SettingIndex, query: &Query) -> Vec<SearchResult> {
+    let mut results: Vec<SearchResult> = index
+        .aliases
+        .iter()
+        .filter_map(|(alias, indexed)| {
+            let matches: Vec<(i64, FieldMatch)> = fields(alias, indexed)
+                .into_iter()
+                .filter_map(|(field, text)| {
+                    let (score, indices) = query.find(&text)?;
+                    Some((score * field.weight(), FieldMatch { field, indices }))
+                })
+                .collect();
+            let score = matches.iter().map(|(score, _)| *score).max()?;
+            Some(SearchResult {
+                alias: alias.clone(),
+                score,
+                matches: matches.into_iter().map(|(_, m)| m).collect(),
+            })
+        })
+        .collect();
+    // stable, so equal scores keep the setting order
+    results.sort_by_key(|r| Reverse(r.score));
+    results
+}
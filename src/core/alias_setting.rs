@@ -1,78 +1,714 @@
-use crate::cmn::files;
+use crate::cmn::{files, strings};
 
-use super::error::{AliasError, ErrorKind};
-use regex::Regex;
+use super::{
+    error::{AliasError, ErrorKind},
+    migration::{self, SETTING_VERSION},
+    setting_document::SettingDocument,
+    setting_format::SettingFormat,
+    setting_index,
+    transaction::Transaction,
+    variable::{RuntimeVariables, Variable},
+};
+use indexmap::IndexMap;
+use regex::{NoExpand, Regex};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet},
+    env,
+    fmt::Display,
     fs::{self},
     io::Write,
-    path::Path,
+    path::{Path, PathBuf},
 };
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+const SETTING_TEMPLATE: &str = include_str!("../../template/alias-setting.toml");
+
+/// Project setting file names, looked up in this order in each directory
+pub const PROJECT_SETTING_NAMES: [&str; 4] = [
+    ".alias-rs.toml",
+    ".alias-rs.yaml",
+    ".alias-rs.yml",
+    ".alias-rs.json",
+];
+
+/// alias-rs setting
+#[derive(Debug, Serialize, Deserialize, Clone, Default, JsonSchema)]
 pub struct AliasSetting {
+    /// setting schema version, a setting without it is version 0
+    #[serde(default)]
+    pub version: u32,
+    /// other setting files merged beneath this one, relative to this file
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    /// generated alias scripts
+    #[serde(default)]
     pub script: Script,
-    pub aliases: HashMap<String, String>,
+    /// values of the `{{name}}` placeholders, overridden by `--define-file` and `--define`
+    #[serde(default)]
+    pub variables: IndexMap<String, String>,
+    /// alias name to its command, or to its command with metadata
+    #[serde(default)]
+    pub aliases: IndexMap<String, AliasDefine>,
+    /// variables used to replace the placeholders, with where each value came from
+    #[serde(skip)]
+    pub resolved_variables: BTreeMap<String, Variable>,
+    /// setting file each alias came from
+    #[serde(skip)]
+    pub alias_sources: HashMap<String, SettingSource>,
+    /// setting files loaded, in load order
+    #[serde(skip)]
+    pub files: Vec<String>,
+    /// project setting loaded, it depends on the current directory
+    #[serde(skip)]
+    pub project_setting: Option<String>,
+    /// hash of the setting files and the runtime variables, to check if a setting index is stale
+    #[serde(skip)]
+    pub hash: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, JsonSchema)]
+pub struct Script {
+    /// directory of the alias scripts, default is `<alias-rs home>/script`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub home: Option<String>,
+    /// environment variable holding the script home (Windows only), default is `ALIAS_SCRIPT_HOME`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub home_env_name: Option<String>,
+    /// how aliases are installed in the script home, default is `script`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<ScriptMode>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ScriptMode {
+    /// a shell (or bat) script per alias
+    #[default]
+    Script,
+    /// a link per alias to the alias-rs binary, which runs the alias from the alias index
+    Multicall,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(untagged)]
+pub enum AliasDefine {
+    /// alias command
+    Command(String),
+    /// alias command with metadata
+    Detail(AliasDetail),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct AliasDetail {
+    /// alias command
+    pub command: String,
+    /// what the alias does
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// tags to group and search aliases
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// how the alias is installed in the script home, default is `script`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<AliasKind>,
 }
 
-impl Default for AliasSetting {
-    fn default() -> Self {
-        Self {
-            script: Script::default(),
-            aliases: HashMap::default(),
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AliasKind {
+    /// a link to the executable if the command is a single executable found in `PATH`,
+    /// otherwise as `script`
+    Auto,
+    /// as the script mode of the setting
+    #[default]
+    Script,
+    /// a link to the executable the command names, fail if not found in `PATH`
+    Link,
+}
+
+impl AliasDefine {
+    pub fn command(&self) -> &String {
+        match self {
+            AliasDefine::Command(command) => command,
+            AliasDefine::Detail(detail) => &detail.command,
+        }
+    }
+
+    pub fn description(&self) -> Option<&String> {
+        match self {
+            AliasDefine::Command(_) => None,
+            AliasDefine::Detail(detail) => detail.description.as_ref(),
+        }
+    }
+
+    pub fn tags(&self) -> &[String] {
+        match self {
+            AliasDefine::Command(_) => &[],
+            AliasDefine::Detail(detail) => &detail.tags,
+        }
+    }
+
+    pub fn kind(&self) -> AliasKind {
+        match self {
+            AliasDefine::Command(_) => AliasKind::default(),
+            AliasDefine::Detail(detail) => detail.kind.unwrap_or_default(),
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Script {
-    pub home: Option<String>,
-    pub home_env_name: Option<String>,
+/// Setting layers, from the lowest to the highest precedence
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SettingLayer {
+    System,
+    User,
+    Project,
 }
 
-impl Default for Script {
-    fn default() -> Self {
-        Self {
-            home: None,
-            home_env_name: None,
+impl Display for SettingLayer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SettingLayer::System => write!(f, "system"),
+            SettingLayer::User => write!(f, "user"),
+            SettingLayer::Project => write!(f, "project"),
         }
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct SettingSource {
+    pub layer: SettingLayer,
+    pub path: String,
+}
+
+impl Display for SettingSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} :: {}", self.layer, self.path)
+    }
+}
+
+/// Only the keys needed before the placeholders are replaced
+#[derive(Deserialize)]
+struct SettingHead {
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
+    variables: IndexMap<String, String>,
+}
+
+struct SettingFile {
+    source: SettingSource,
+    /// included by another file, rather than a layer itself
+    included: bool,
+    format: SettingFormat,
+    content: String,
+    head: SettingHead,
+}
+
+/// Load and merge the setting layers, a later one overrides an earlier one:
+///
+/// 1. system setting (`system_setting_path`), if present
+/// 2. user setting (`setting_path`), must be present
+/// 3. project setting, the nearest `.alias-rs.toml` (or `.yaml`, `.yml`, `.json`) upward from
+///    the current directory
+///
+/// Each file is parsed in the format of its extension, TOML by default.
+/// Files listed in `include` are merged beneath the file including them, in list order.
+/// `script` and `variables` keys and aliases are overridden one by one, an overridden alias
+/// keeps its place and a new one is appended, so aliases keep the order of the files.
+/// Only the system and user setting themselves may set `script`, as the project setting comes
+/// from whatever directory the user is in, and included files may be shared the same way.
 pub fn load(
+    system_setting_path: &str,
     setting_path: &String,
-    runtime_variables: &HashMap<String, String>,
+    runtime_variables: &RuntimeVariables,
 ) -> Result<AliasSetting, AliasError> {
-    // get setting content
-    let path = Path::new(&setting_path);
-    let mut content = if path.exists() {
-        fs::read_to_string(path).map_err(|e| AliasError {
+    load_layers(
+        system_setting_path,
+        setting_path,
+        find_project_setting(),
+        runtime_variables,
+    )
+}
+
+/// Load the system and user setting only, the same in any directory, as multicall links
+/// look up their commands in it
+pub fn load_without_project(
+    system_setting_path: &str,
+    setting_path: &String,
+    runtime_variables: &RuntimeVariables,
+) -> Result<AliasSetting, AliasError> {
+    load_layers(system_setting_path, setting_path, None, runtime_variables)
+}
+
+fn load_layers(
+    system_setting_path: &str,
+    setting_path: &String,
+    project_setting: Option<String>,
+    runtime_variables: &RuntimeVariables,
+) -> Result<AliasSetting, AliasError> {
+    // get setting contents, from the lowest precedence to the highest
+    let mut setting_files = Vec::new();
+    let mut visited = HashSet::new();
+    if Path::new(system_setting_path).is_file() {
+        read_layer(
+            SettingLayer::System,
+            system_setting_path,
+            false,
+            &mut visited,
+            &mut setting_files,
+        )?;
+    }
+    if !Path::new(setting_path).exists() {
+        return Err(AliasError {
             kind: ErrorKind::Unkonw,
-            msg: format!("read setting fail :: {}", e),
-        })?
-    } else {
-        // not exist
-        let content = toml::to_string_pretty(&AliasSetting::default()).map_err(|e| AliasError {
+            msg: not_found_message(setting_path),
+        });
+    }
+    read_layer(
+        SettingLayer::User,
+        setting_path,
+        false,
+        &mut visited,
+        &mut setting_files,
+    )?;
+    if let Some(project_setting_path) = &project_setting {
+        read_layer(
+            SettingLayer::Project,
+            project_setting_path,
+            false,
+            &mut visited,
+            &mut setting_files,
+        )?;
+    }
+    // merge setting variables with runtime variables
+    let setting_variables = setting_files
+        .iter()
+        .map(|f| (f.source.path.clone(), f.head.variables.clone()))
+        .collect();
+    let resolved_variables = runtime_variables.resolve(setting_variables);
+    let files = setting_files
+        .iter()
+        .map(|f| f.source.path.clone())
+        .collect();
+    let hash = setting_index::hash(
+        setting_files
+            .iter()
+            .map(|f| (f.source.path.as_str(), f.content.as_bytes())),
+        runtime_variables,
+    );
+    // replace placeholder with variables, then deserialize and merge each setting
+    let mut setting = AliasSetting::default();
+    for setting_file in setting_files {
+        let mut content = setting_file.content;
+        for (name, variable) in &resolved_variables {
+            let regex = Regex::new(&format!("\\{{\\{{{}\\}}\\}}", name)).unwrap();
+            content = regex
+                .replace_all(&content, NoExpand(&variable.value))
+                .to_string();
+        }
+        let mut table = setting_file
+            .format
+            .parse_document(&content)
+            .map_err(|e| AliasError {
+                kind: ErrorKind::Unkonw,
+                msg: format!(
+                    "deserialize setting fail :: {} :: {}",
+                    setting_file.source.path, e
+                ),
+            })?;
+        migration::migrate(&mut table, &setting_file.source.path)?;
+        let layer_setting: AliasSetting =
+            toml::Value::Table(table)
+                .try_into()
+                .map_err(|e| AliasError {
+                    kind: ErrorKind::Unkonw,
+                    msg: format!(
+                        "deserialize setting fail :: {} :: {}",
+                        setting_file.source.path, e
+                    ),
+                })?;
+        let script = &layer_setting.script;
+        let sets_script =
+            script.home.is_some() || script.home_env_name.is_some() || script.mode.is_some();
+        if sets_script
+            && (setting_file.included || setting_file.source.layer == SettingLayer::Project)
+        {
+            return Err(AliasError {
+                kind: ErrorKind::Unkonw,
+                msg: format!(
+                    "`script` is only allowed in the system and user setting, not in a project or included setting :: {}",
+                    setting_file.source.path
+                ),
+            });
+        }
+        setting.merge(layer_setting, &setting_file.source);
+    }
+    setting.resolved_variables = resolved_variables;
+    setting.files = files;
+    setting.project_setting = project_setting;
+    setting.hash = hash;
+    Ok(setting)
+}
+
+impl AliasSetting {
+    fn merge(&mut self, other: AliasSetting, source: &SettingSource) {
+        self.version = other.version;
+        if other.script.home.is_some() {
+            self.script.home = other.script.home;
+        }
+        if other.script.home_env_name.is_some() {
+            self.script.home_env_name = other.script.home_env_name;
+        }
+        if other.script.mode.is_some() {
+            self.script.mode = other.script.mode;
+        }
+        self.variables.extend(other.variables);
+        for (alias, define) in other.aliases {
+            self.alias_sources.insert(alias.clone(), source.clone());
+            self.aliases.insert(alias, define);
+        }
+    }
+}
+
+/// Directory of a setting file, where its lock and journal are kept
+pub fn get_setting_dir(setting_path: &str) -> String {
+    Path::new(setting_path)
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .map_or(".".to_owned(), |p| p.to_string_lossy().to_string())
+}
+
+/// Create a default setting if not present, used by the operations writing aliases
+pub fn create_default_if_missing(setting_path: &String) -> Result<(), AliasError> {
+    if Path::new(setting_path).exists() {
+        return Ok(());
+    }
+    let default_setting = AliasSetting {
+        version: SETTING_VERSION,
+        ..Default::default()
+    };
+    let content = SettingFormat::from_path(setting_path)
+        .serialize(&default_setting)
+        .map_err(|e| AliasError {
             kind: ErrorKind::Unkonw,
             msg: format!("serialize default setting fail :: {}", e),
         })?;
-        files::create_new_with_all_dir(setting_path)
-            .and_then(|mut f| f.write_all(content.as_bytes()))
-            .map_err(|e| AliasError {
+    files::create_new_with_all_dir(setting_path)
+        .and_then(|mut f| f.write_all(content.as_bytes()))
+        .map_err(|e| AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!("create default setting fail :: {}", e),
+        })
+}
+
+/// Create a setting from the commented template, in the format of the path extension
+/// (comments are only kept for TOML)
+pub fn stage_new_setting(tx: &mut Transaction, setting_path: &String) -> Result<(), AliasError> {
+    if Path::new(setting_path).exists() {
+        return Err(AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!("setting already exists :: {}", setting_path),
+        });
+    }
+    let content = match SettingFormat::from_path(setting_path) {
+        SettingFormat::Toml => SETTING_TEMPLATE.to_owned(),
+        format => {
+            let table = SettingFormat::Toml
+                .parse_document(SETTING_TEMPLATE)
+                .map_err(|e| AliasError {
+                    kind: ErrorKind::Unkonw,
+                    msg: format!("deserialize setting template fail :: {}", e),
+                })?;
+            format.serialize(&table).map_err(|e| AliasError {
                 kind: ErrorKind::Unkonw,
-                msg: format!("create default setting fail :: {}", e),
-            })?;
-        content
+                msg: format!("serialize setting fail :: {}", e),
+            })?
+        }
+    };
+    tx.write(setting_path.to_owned(), content.into_bytes());
+    Ok(())
+}
+
+/// Error message for a missing setting, with a similar setting file nearby if any
+pub fn not_found_message(setting_path: &String) -> String {
+    let path = Path::new(setting_path);
+    let file_name = path
+        .file_name()
+        .map_or(String::default(), |f| f.to_string_lossy().to_string());
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
     };
-    // replace placeholder with runtime variables
-    for (name, val) in runtime_variables {
-        let regex = Regex::new(&format!("\\{{\\{{{}\\}}\\}}", name)).unwrap();
-        content = regex.replace_all(&content, val).to_string();
+    let candidates: Vec<String> = files::list_dir(&dir.to_string_lossy().to_string())
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|name| {
+            [".toml", ".yaml", ".yml", ".json"]
+                .iter()
+                .any(|ext| name.ends_with(ext))
+        })
+        .collect();
+    match strings::most_similar(&file_name, &candidates) {
+        Some(similar) => format!(
+            "setting not found :: {} (did you mean {}?)",
+            setting_path,
+            dir.join(similar).display()
+        ),
+        None => format!(
+            "setting not found :: {} (create it with `alias-rs new-setting`, `alias-rs init` or `alias-rs set`)",
+            setting_path
+        ),
     }
-    // deserialize setting
-    Ok(toml::from_str(&content).map_err(|e| AliasError {
+}
+
+/// Stage setting an alias command in a setting file, keeping the alias metadata
+pub fn stage_alias_command(
+    tx: &mut Transaction,
+    setting_path: &String,
+    alias: &str,
+    command: &str,
+) -> Result<(), AliasError> {
+    edit_setting(tx, setting_path, |doc| {
+        doc.set_alias_command(alias, command);
+        true
+    })
+}
+
+/// Stage removing an alias from a setting file, nothing is staged if it is not present
+pub fn stage_alias_removal(
+    tx: &mut Transaction,
+    setting_path: &String,
+    alias: &str,
+) -> Result<(), AliasError> {
+    edit_setting(tx, setting_path, |doc| doc.remove_alias(alias).is_some())
+}
+
+/// Stage copying an alias in a setting file with its metadata, removing the source if `rename`.
+/// `define` is used if the source is not in this setting, e.g. it comes from another layer
+pub fn stage_alias_copy(
+    tx: &mut Transaction,
+    setting_path: &String,
+    source: &str,
+    target: &str,
+    define: &AliasDefine,
+    rename: bool,
+) -> Result<(), AliasError> {
+    let define = toml::Value::try_from(define).map_err(|e| AliasError {
         kind: ErrorKind::Unkonw,
-        msg: format!("deserialize setting fail :: {}", e),
-    })?)
+        msg: format!("serialize alias define fail :: {} :: {}", source, e),
+    })?;
+    let mut copied = false;
+    edit_setting(tx, setting_path, |doc| {
+        copied = doc.copy_alias(source, target);
+        if !copied {
+            doc.set_alias(target, define);
+        }
+        if rename {
+            doc.remove_alias(source);
+        }
+        true
+    })?;
+    if rename && !copied {
+        return Err(AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!(
+                "alias is not defined in the user setting, only its aliases can be renamed, use `alias-rs copy` instead :: {} :: {}",
+                source, setting_path
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// Command of an alias as written, with its `{{name}}` placeholders, so editing it keeps them.
+/// It is read from the user setting as staged in the transaction, or else from the setting file
+/// the alias came from, `None` if the alias is not defined
+pub fn raw_alias_command(
+    tx: &Transaction,
+    setting: &AliasSetting,
+    setting_path: &String,
+    alias: &str,
+) -> Result<Option<String>, AliasError> {
+    if let Some(command) = read_setting(tx, setting_path)?.alias_command(alias) {
+        return Ok(Some(command));
+    }
+    match setting.alias_sources.get(alias) {
+        Some(source) if source.path != *setting_path => {
+            Ok(read_setting(tx, &source.path)?.alias_command(alias))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Edit a setting file as staged in the transaction, `edit` returns whether it changed anything
+fn edit_setting<F>(tx: &mut Transaction, setting_path: &String, edit: F) -> Result<(), AliasError>
+where
+    F: FnOnce(&mut SettingDocument) -> bool,
+{
+    let mut doc = read_setting(tx, setting_path)?;
+    if !edit(&mut doc) {
+        return Ok(());
+    }
+    let content = doc.serialize().map_err(|e| AliasError {
+        kind: ErrorKind::Unkonw,
+        msg: format!("serialize setting fail :: {}", e),
+    })?;
+    tx.write(setting_path.to_owned(), content.into_bytes());
+    Ok(())
+}
+
+/// A setting file as staged in the transaction, opened for editing
+fn read_setting(tx: &Transaction, setting_path: &String) -> Result<SettingDocument, AliasError> {
+    let content = match tx.read(setting_path)? {
+        Some(content) => String::from_utf8(content).map_err(|e| AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!("read setting fail :: {} :: {}", setting_path, e),
+        })?,
+        None => {
+            return Err(AliasError {
+                kind: ErrorKind::Unkonw,
+                msg: not_found_message(setting_path),
+            })
+        }
+    };
+    SettingDocument::parse(SettingFormat::from_path(setting_path), &content).map_err(|e| {
+        AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!("deserialize setting fail :: {} :: {}", setting_path, e),
+        }
+    })
+}
+
+/// Upgrade a setting file to `SETTING_VERSION`, the original is kept as
+/// `<setting>.v<version>.bak`. Return the version before migration and the backup path,
+/// or `None` if it is up to date
+pub fn stage_migrate(
+    tx: &mut Transaction,
+    setting_path: &String,
+) -> Result<Option<(u32, String)>, AliasError> {
+    let content = fs::read_to_string(setting_path).map_err(|e| AliasError {
+        kind: ErrorKind::Unkonw,
+        msg: format!("read setting fail :: {} :: {}", setting_path, e),
+    })?;
+    let format = SettingFormat::from_path(setting_path);
+    let mut table = format.parse_document(&content).map_err(|e| AliasError {
+        kind: ErrorKind::Unkonw,
+        msg: format!("deserialize setting fail :: {} :: {}", setting_path, e),
+    })?;
+    let version = migration::migrate(&mut table, setting_path)?;
+    if version == SETTING_VERSION {
+        return Ok(None);
+    }
+    let backup_path = format!("{}.v{}.bak", setting_path, version);
+    let migrated = format.serialize(&table).map_err(|e| AliasError {
+        kind: ErrorKind::Unkonw,
+        msg: format!("serialize setting fail :: {}", e),
+    })?;
+    tx.write(backup_path.clone(), content.into_bytes());
+    tx.write(setting_path.to_owned(), migrated.into_bytes());
+    Ok(Some((version, backup_path)))
+}
+
+/// Convert a setting file to the format of `target_path`, keeping the keys order and the
+/// placeholders, comments are not kept
+pub fn stage_convert(
+    tx: &mut Transaction,
+    setting_path: &String,
+    target_path: &String,
+) -> Result<(), AliasError> {
+    if Path::new(target_path).exists() {
+        return Err(AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!("convert target already exists :: {}", target_path),
+        });
+    }
+    let content = fs::read_to_string(setting_path).map_err(|e| AliasError {
+        kind: ErrorKind::Unkonw,
+        msg: format!("read setting fail :: {} :: {}", setting_path, e),
+    })?;
+    let table = SettingFormat::from_path(setting_path)
+        .parse_document(&content)
+        .map_err(|e| AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!("deserialize setting fail :: {} :: {}", setting_path, e),
+        })?;
+    let content = SettingFormat::from_path(target_path)
+        .serialize(&table)
+        .map_err(|e| AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!("serialize setting fail :: {}", e),
+        })?;
+    tx.write(target_path.to_owned(), content.into_bytes());
+    Ok(())
+}
+
+/// Read a setting file and, before it, the files it includes
+fn read_layer(
+    layer: SettingLayer,
+    path: &str,
+    included: bool,
+    visited: &mut HashSet<PathBuf>,
+    setting_files: &mut Vec<SettingFile>,
+) -> Result<(), AliasError> {
+    // a file included more than once is only merged at its first place
+    let canonical_path = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+    if !visited.insert(canonical_path) {
+        return Ok(());
+    }
+    let content = fs::read_to_string(path).map_err(|e| AliasError {
+        kind: ErrorKind::Unkonw,
+        msg: format!("read setting fail :: {} :: {}", path, e),
+    })?;
+    let format = SettingFormat::from_path(path);
+    let head = format
+        .parse::<SettingHead>(&content)
+        .map_err(|e| AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!("deserialize setting fail :: {} :: {}", path, e),
+        })?;
+    let base_dir = Path::new(path).parent().unwrap_or(Path::new(""));
+    for include in &head.include {
+        let include_path = base_dir.join(include);
+        if !include_path.is_file() {
+            return Err(AliasError {
+                kind: ErrorKind::Unkonw,
+                msg: format!(
+                    "setting include not exists :: {} :: included by {}",
+                    include_path.display(),
+                    path
+                ),
+            });
+        }
+        read_layer(
+            layer,
+            &include_path.to_string_lossy(),
+            true,
+            visited,
+            setting_files,
+        )?;
+    }
+    setting_files.push(SettingFile {
+        source: SettingSource {
+            layer,
+            path: path.to_owned(),
+        },
+        included,
+        format,
+        content,
+        head,
+    });
+    Ok(())
+}
+
+/// Nearest project setting upward from the current directory
+pub fn find_project_setting() -> Option<String> {
+    let current_dir = env::current_dir().ok()?;
+    current_dir
+        .ancestors()
+        .flat_map(|dir| PROJECT_SETTING_NAMES.map(|name| dir.join(name)))
+        .find(|path| path.is_file())
+        .map(|path| path.to_string_lossy().to_string())
 }
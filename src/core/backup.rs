@@ -0,0 +1,442 @@
+use super::{
+    alias::Alias,
+    alias_history,
+    alias_setting::{self, AliasSetting},
+    error::{AliasError, ErrorKind},
+    transaction::{FileState, Transaction},
+};
+use crate::cmn::files;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::Read,
+    path::{Component, Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// 2 adds the included settings and the alias history
+const BACKUP_VERSION: u32 = 2;
+const MANIFEST_NAME: &str = "manifest.json";
+
+/// The alias-rs part of the system env, e.g. the shell profile block or user env vars
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EnvSnapshot {
+    /// alias-rs block of the shell profile, `block` is `None` if the profile has none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<ProfileSnapshot>,
+    /// user environment variables, `None` if unset
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vars: Vec<(String, Option<String>)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProfileSnapshot {
+    /// profile the block was found in, only informative, restore writes the current profile
+    pub path: String,
+    pub block: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    version: u32,
+    /// seconds since unix epoch
+    created: u64,
+    setting_path: String,
+    script_home: String,
+    /// id of the last journal entry when created
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    journal_id: Option<u64>,
+    files: Vec<ManifestFile>,
+    env: EnvSnapshot,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ManifestFile {
+    /// entry name in the archive, `setting/`, `script/` or `history/` and the file path
+    /// relative to that dir, the file is restored there in the current setting dir or script home
+    entry: String,
+    /// where the file was when backed up, only informational
+    path: String,
+    sha256: String,
+    /// target of a symbolic link, e.g. a multicall link, which has no entry in the archive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    link: Option<String>,
+}
+
+/// Archive the user setting with the settings it includes in its dir, all alias scripts,
+/// the alias history and the system env into a `.tar.zst` file, return the number of files archived
+pub fn create(alias: &dyn Alias, output_path: &String) -> Result<usize, AliasError> {
+    if Path::new(output_path).exists() {
+        return Err(AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!("backup file already exists :: {}", output_path),
+        });
+    }
+    let setting_path = alias.setting_path();
+    let setting_dir = alias_setting::get_setting_dir(&setting_path);
+    let setting = alias.setting();
+    let script_home = setting.script.home.clone().unwrap();
+    let mut sources = vec![(
+        format!("setting/{}", file_name(&setting_path)),
+        setting_path.clone(),
+    )];
+    for include in included_settings(&setting, &setting_path) {
+        // only includes in the setting dir, so they are restored beside the setting
+        if let Some(relative) = relative_to(&include, &setting_dir) {
+            sources.push((format!("setting/{}", relative), include));
+        }
+    }
+    let history_dir = alias_history::history_dir(&setting_dir);
+    for history in list_dir(&history_dir)? {
+        sources.push((
+            format!("history/{}", history),
+            Path::new(&history_dir)
+                .join(&history)
+                .to_string_lossy()
+                .to_string(),
+        ));
+    }
+    for script in alias.list()?.unwrap_or_default() {
+        sources.push((
+            format!("script/{}", script),
+            Path::new(&script_home)
+                .join(&script)
+                .to_string_lossy()
+                .to_string(),
+        ));
+    }
+    let mut files = Vec::new();
+    let mut contents = Vec::new();
+    for (entry, path) in sources {
+        match FileState::read(&path)? {
+            Some(FileState::Content(content)) => {
+                files.push(ManifestFile {
+                    entry: entry.clone(),
+                    path,
+                    sha256: sha256(&content),
+                    link: None,
+                });
+                contents.push((entry, content));
+            }
+            Some(FileState::Link(target)) => files.push(ManifestFile {
+                entry,
+                path,
+                sha256: String::default(),
+                link: Some(target),
+            }),
+            None => {}
+        }
+    }
+    let manifest = Manifest {
+        version: BACKUP_VERSION,
+        created: now(),
+        setting_path,
+        script_home,
+        journal_id: alias.journal().entries()?.last().map(|e| e.id),
+        files,
+        env: alias.snapshot_env()?,
+    };
+    let manifest_content = serde_json::to_vec_pretty(&manifest).map_err(|e| AliasError {
+        kind: ErrorKind::Unkonw,
+        msg: format!("serialize backup manifest fail :: {}", e),
+    })?;
+    let count = manifest.files.len();
+    write_archive(
+        output_path,
+        manifest.created,
+        [(MANIFEST_NAME.to_owned(), manifest_content)]
+            .into_iter()
+            .chain(contents),
+    )
+    .map_err(|e| {
+        let _ = fs::remove_file(output_path);
+        AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!("write backup fail :: {} :: {}", output_path, e),
+        }
+    })?;
+    Ok(count)
+}
+
+/// Stage restoring a backup onto the current setting dir and script home, wherever they were
+/// when backed up. Scripts and alias history not in the backup are removed.
+/// Fails if the backup is corrupted or has a file elsewhere, or, unless `force`,
+/// if the current state is newer than it
+pub fn stage_restore(
+    alias: &dyn Alias,
+    tx: &mut Transaction,
+    backup_path: &String,
+    force: bool,
+) -> Result<(), AliasError> {
+    let mut entries = read_archive(backup_path).map_err(|e| AliasError {
+        kind: ErrorKind::Unkonw,
+        msg: format!("read backup fail :: {} :: {}", backup_path, e),
+    })?;
+    let manifest: Manifest = entries
+        .remove(MANIFEST_NAME)
+        .ok_or_else(|| format!("{} is missing", MANIFEST_NAME))
+        .and_then(|content| serde_json::from_slice(&content).map_err(|e| e.to_string()))
+        .map_err(|e| AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!("invalid backup manifest :: {} :: {}", backup_path, e),
+        })?;
+    if manifest.version > BACKUP_VERSION {
+        return Err(AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!(
+                "backup version is newer than this alias-rs supports, please upgrade alias-rs :: {} :: version={}, supported={}",
+                backup_path, manifest.version, BACKUP_VERSION
+            ),
+        });
+    }
+    // verify every file before changing anything
+    let setting_path = alias.setting_path();
+    let setting_dir = alias_setting::get_setting_dir(&setting_path);
+    let script_home = alias.setting().script.home.unwrap();
+    let history_dir = alias_history::history_dir(&setting_dir);
+    let mut restored = Vec::new();
+    for file in &manifest.files {
+        let path = restore_path(
+            &file.entry,
+            &manifest.setting_path,
+            &setting_path,
+            &setting_dir,
+            &script_home,
+            &history_dir,
+        )
+        .ok_or_else(|| AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!(
+                "backup file is not a setting, script or alias history, nothing restored :: {} :: {}",
+                backup_path, file.entry
+            ),
+        })?;
+        if let Some(target) = &file.link {
+            restored.push((path, FileState::Link(target.clone())));
+            continue;
+        }
+        let content = entries.remove(&file.entry).ok_or_else(|| AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!(
+                "backup file is missing :: {} :: {}",
+                backup_path, file.entry
+            ),
+        })?;
+        if sha256(&content) != file.sha256 {
+            return Err(AliasError {
+                kind: ErrorKind::Unkonw,
+                msg: format!(
+                    "backup checksum mismatch, the backup is corrupted :: {} :: {}",
+                    backup_path, file.entry
+                ),
+            });
+        }
+        restored.push((path, FileState::Content(content)));
+    }
+    // scripts now in the script home but not in the backup, and the alias history,
+    // if backed up
+    let mut removed = Vec::new();
+    let mut current: Vec<String> = alias
+        .list()?
+        .unwrap_or_default()
+        .iter()
+        .map(|script| {
+            Path::new(&script_home)
+                .join(script)
+                .to_string_lossy()
+                .to_string()
+        })
+        .collect();
+    if manifest.version >= 2 {
+        for history in list_dir(&history_dir)? {
+            current.push(
+                Path::new(&history_dir)
+                    .join(history)
+                    .to_string_lossy()
+                    .to_string(),
+            );
+        }
+    }
+    for path in current {
+        if !restored.iter().any(|(p, _)| p == &path) {
+            removed.push(path);
+        }
+    }
+    if !force {
+        let newer = newer_than(alias, &manifest, &restored, &removed)?;
+        if !newer.is_empty() {
+            return Err(AliasError {
+                kind: ErrorKind::Unkonw,
+                msg: format!(
+                    "current state is newer than the backup, pass --force to overwrite it :: {}",
+                    newer.join(", ")
+                ),
+            });
+        }
+    }
+    for (path, state) in restored {
+        match state {
+            FileState::Content(content) => tx.write(path, content),
+            FileState::Link(target) => tx.link(path, target),
+        }
+    }
+    for path in removed {
+        tx.remove(path);
+    }
+    alias.stage_env_restore(tx, &manifest.env)
+}
+
+/// Files and operations changed after the backup was created
+fn newer_than(
+    alias: &dyn Alias,
+    manifest: &Manifest,
+    restored: &[(String, FileState)],
+    removed: &[String],
+) -> Result<Vec<String>, AliasError> {
+    let mut newer = Vec::new();
+    let changed = restored
+        .iter()
+        .filter(|(path, state)| {
+            FileState::read(path).is_ok_and(|current| current.is_some_and(|c| &c != state))
+        })
+        .map(|(path, _)| path)
+        .chain(removed);
+    for path in changed {
+        let modified = fs::symlink_metadata(path)
+            .and_then(|m| m.modified())
+            .map_or(0, |t| {
+                t.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+            });
+        if modified > manifest.created {
+            newer.push(path.clone());
+        }
+    }
+    if let Some(entry) = alias.journal().entries()?.last() {
+        if manifest.journal_id.is_none_or(|id| entry.id > id) {
+            newer.push(format!("operation #{} `{}`", entry.id, entry.operation));
+        }
+    }
+    Ok(newer)
+}
+
+/// Where a backup entry is restored to, `None` if it is not a setting in the setting dir,
+/// a file in the script home or an alias history file
+fn restore_path(
+    entry: &str,
+    backup_setting_path: &str,
+    setting_path: &str,
+    setting_dir: &str,
+    script_home: &str,
+    history_dir: &str,
+) -> Option<String> {
+    let (dir, relative) = entry.split_once('/')?;
+    let components: Vec<Component> = Path::new(relative).components().collect();
+    if components.is_empty() || !components.iter().all(|c| matches!(c, Component::Normal(_))) {
+        return None;
+    }
+    let single = components.len() == 1;
+    let path = match dir {
+        // the user setting may have another name now
+        "setting" if relative == file_name(backup_setting_path) => PathBuf::from(setting_path),
+        "setting" if is_setting_file(relative) => Path::new(setting_dir).join(relative),
+        "script" if single => Path::new(script_home).join(relative),
+        "history" if single && relative.ends_with(".jsonl") => {
+            Path::new(history_dir).join(relative)
+        }
+        _ => return None,
+    };
+    Some(path.to_string_lossy().to_string())
+}
+
+/// Setting files loaded other than the user and project setting, e.g. included ones
+fn included_settings(setting: &AliasSetting, setting_path: &str) -> Vec<String> {
+    let project_setting = alias_setting::find_project_setting();
+    setting
+        .files
+        .iter()
+        .filter(|path| *path != setting_path && Some(*path) != project_setting.as_ref())
+        .cloned()
+        .collect()
+}
+
+/// Path relative to a dir, `None` if it is not in the dir
+fn relative_to(path: &str, dir: &str) -> Option<String> {
+    let path = fs::canonicalize(path).ok()?;
+    let dir = fs::canonicalize(dir).ok()?;
+    let relative = path.strip_prefix(dir).ok()?;
+    Some(relative.to_string_lossy().replace('\\', "/"))
+}
+
+fn is_setting_file(name: &str) -> bool {
+    [".toml", ".yaml", ".yml", ".json"]
+        .iter()
+        .any(|ext| name.ends_with(ext))
+}
+
+fn list_dir(dir: &String) -> Result<Vec<String>, AliasError> {
+    files::list_dir(dir)
+        .map(|list| list.unwrap_or_default())
+        .map_err(|e| AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!("list dir fail :: {} :: {}", dir, e),
+        })
+}
+
+fn write_archive(
+    output_path: &String,
+    mtime: u64,
+    entries: impl Iterator<Item = (String, Vec<u8>)>,
+) -> Result<(), std::io::Error> {
+    if let Some(parent) = Path::new(output_path).parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let encoder = zstd::Encoder::new(File::create_new(output_path)?, 0)?;
+    let mut builder = tar::Builder::new(encoder);
+    for (name, content) in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+        header.set_cksum();
+        builder.append_data(&mut header, name, content.as_slice())?;
+    }
+    builder.into_inner()?.finish()?;
+    Ok(())
+}
+
+fn read_archive(backup_path: &String) -> Result<HashMap<String, Vec<u8>>, std::io::Error> {
+    let decoder = zstd::Decoder::new(File::open(backup_path)?)?;
+    let mut archive = tar::Archive::new(decoder);
+    let mut entries = HashMap::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().to_string();
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content)?;
+        entries.insert(name, content);
+    }
+    Ok(entries)
+}
+
+fn sha256(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map_or(String::default(), |n| n.to_string_lossy().to_string())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Default backup file name, e.g. `alias-rs-backup-1700000000.tar.zst`
+pub fn default_backup_name() -> String {
+    format!("alias-rs-backup-{}.tar.zst", now())
+}
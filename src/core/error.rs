@@ -1,3 +1,4 @@
+#[derive(Debug)]
 pub struct AliasError {
     pub kind: ErrorKind,
     pub msg: String,
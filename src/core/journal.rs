@@ -0,0 +1,206 @@
+use super::{
+    error::{AliasError, ErrorKind},
+    transaction::{FileState, Transaction},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const JOURNAL_FILE_NAME: &str = "journal.jsonl";
+
+/// File content kept in the journal, text if it is UTF-8
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Content {
+    Text(String),
+    Bytes(Vec<u8>),
+    /// a symbolic link, to the path
+    Link {
+        link: String,
+    },
+}
+
+impl From<FileState> for Content {
+    fn from(state: FileState) -> Self {
+        match state {
+            FileState::Content(bytes) => match String::from_utf8(bytes) {
+                Ok(text) => Content::Text(text),
+                Err(e) => Content::Bytes(e.into_bytes()),
+            },
+            FileState::Link(link) => Content::Link { link },
+        }
+    }
+}
+
+impl From<Content> for FileState {
+    fn from(content: Content) -> Self {
+        match content {
+            Content::Text(text) => FileState::Content(text.into_bytes()),
+            Content::Bytes(bytes) => FileState::Content(bytes),
+            Content::Link { link } => FileState::Link(link),
+        }
+    }
+}
+
+/// A file before and after an operation, `None` if absent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileRecord {
+    pub path: String,
+    pub before: Option<Content>,
+    pub after: Option<Content>,
+}
+
+/// A user environment variable before and after an operation, `None` if unset
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnvRecord {
+    pub name: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+/// Changes done by a committed transaction
+#[derive(Debug, Clone, Default)]
+pub struct Changes {
+    pub files: Vec<FileRecord>,
+    pub envs: Vec<EnvRecord>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub id: u64,
+    /// seconds since unix epoch
+    pub time: u64,
+    /// the command line of the operation, e.g. `set gs "git status"`
+    pub operation: String,
+    /// ids of the entries reverted by this one, if it is an undo
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub undoes: Vec<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<FileRecord>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub envs: Vec<EnvRecord>,
+}
+
+/// Append-only log of the operations changing aliases, one JSON entry per line
+pub struct Journal {
+    path: String,
+}
+
+impl Journal {
+    /// The journal beside a setting file
+    pub fn new(setting_dir: &str) -> Self {
+        Self {
+            path: Path::new(setting_dir)
+                .join(JOURNAL_FILE_NAME)
+                .to_string_lossy()
+                .to_string(),
+        }
+    }
+
+    /// All entries, oldest first
+    pub fn entries(&self) -> Result<Vec<Entry>, AliasError> {
+        if !Path::new(&self.path).is_file() {
+            return Ok(Vec::new());
+        }
+        let content = fs::read_to_string(&self.path).map_err(|e| AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!("read journal fail :: {} :: {}", self.path, e),
+        })?;
+        content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                serde_json::from_str(line).map_err(|e| AliasError {
+                    kind: ErrorKind::Unkonw,
+                    msg: format!("parse journal fail :: {}:{} :: {}", self.path, index + 1, e),
+                })
+            })
+            .collect()
+    }
+
+    /// Record an operation, nothing is recorded if it changed nothing
+    pub fn append(
+        &self,
+        operation: String,
+        undoes: Vec<u64>,
+        changes: Changes,
+    ) -> Result<Option<u64>, AliasError> {
+        if changes.files.is_empty() && changes.envs.is_empty() {
+            return Ok(None);
+        }
+        let id = self.entries()?.last().map_or(1, |e| e.id + 1);
+        let entry = Entry {
+            id,
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            operation,
+            undoes,
+            files: changes.files,
+            envs: changes.envs,
+        };
+        let line = serde_json::to_string(&entry).map_err(|e| AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!("serialize journal entry fail :: {}", e),
+        })?;
+        if let Some(parent) = Path::new(&self.path).parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut f| writeln!(f, "{}", line))
+            .map_err(|e| AliasError {
+                kind: ErrorKind::Unkonw,
+                msg: format!("write journal fail :: {} :: {}", self.path, e),
+            })?;
+        Ok(Some(id))
+    }
+}
+
+/// Entries not undone yet and not undo themselves, newest first
+pub fn undoable(entries: &[Entry]) -> Vec<&Entry> {
+    let undone: Vec<u64> = entries.iter().flat_map(|e| e.undoes.clone()).collect();
+    entries
+        .iter()
+        .rev()
+        .filter(|e| e.undoes.is_empty() && !undone.contains(&e.id))
+        .collect()
+}
+
+/// Stage reverting entries, newest first, failing if a file was changed by something else since
+pub fn stage_undo(tx: &mut Transaction, entries: &[&Entry]) -> Result<(), AliasError> {
+    for entry in entries {
+        for record in &entry.files {
+            let current = tx.state(&record.path)?;
+            if current != record.after.clone().map(FileState::from) {
+                return Err(AliasError {
+                    kind: ErrorKind::Unkonw,
+                    msg: format!(
+                        "file changed after operation #{} `{}`, can not undo it :: {}",
+                        entry.id, entry.operation, record.path
+                    ),
+                });
+            }
+            match record.before.clone().map(FileState::from) {
+                Some(FileState::Content(content)) => tx.write(record.path.clone(), content),
+                Some(FileState::Link(target)) => tx.link(record.path.clone(), target),
+                None => tx.remove(record.path.clone()),
+            }
+        }
+        for record in &entry.envs {
+            tx.set_user_env(
+                record.name.clone(),
+                record.after.clone(),
+                record.before.clone(),
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use super::error::{AliasError, ErrorKind};
+use std::{
+    fs::{self, File, OpenOptions, TryLockError},
+    io::{Read, Seek, Write},
+    path::{Path, PathBuf},
+    process, thread,
+    time::{Duration, Instant},
+};
+
+const LOCK_FILE_NAME: &str = ".alias-rs.lock";
+const RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// An advisory lock of a directory, held until dropped.
+///
+/// The lock file keeps the PID of its holder, so a busy lock can tell who holds it.
+#[derive(Debug)]
+pub struct DirLock {
+    _file: File,
+}
+
+impl DirLock {
+    /// Lock a directory, creating it if missing.
+    /// `wait` is how long to wait for a busy lock, `None` fails at once
+    pub fn acquire(dir: &Path, wait: Option<Duration>) -> Result<Self, AliasError> {
+        fs::create_dir_all(dir).map_err(|e| AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!("create lock dir fail :: {} :: {}", dir.display(), e),
+        })?;
+        let path = dir.join(LOCK_FILE_NAME);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(|e| AliasError {
+                kind: ErrorKind::Unkonw,
+                msg: format!("open lock file fail :: {} :: {}", path.display(), e),
+            })?;
+        let started = Instant::now();
+        loop {
+            match file.try_lock() {
+                Ok(()) => break,
+                Err(TryLockError::WouldBlock) => {
+                    if wait.is_some_and(|wait| started.elapsed() < wait) {
+                        thread::sleep(RETRY_INTERVAL);
+                        continue;
+                    }
+                    return Err(AliasError {
+                        kind: ErrorKind::Unkonw,
+                        msg: format!(
+                            "setting is locked by another alias-rs process, pid={} :: {} (retry later, or pass --wait to wait for it)",
+                            read_holder(&path),
+                            path.display()
+                        ),
+                    });
+                }
+                Err(TryLockError::Error(e)) => {
+                    return Err(AliasError {
+                        kind: ErrorKind::Unkonw,
+                        msg: format!("lock fail :: {} :: {}", path.display(), e),
+                    })
+                }
+            }
+        }
+        // the lock is released on close, whatever left in the file is only informative
+        let _ = file
+            .set_len(0)
+            .and_then(|_| file.rewind())
+            .and_then(|_| write!(file, "{}", process::id()))
+            .and_then(|_| file.flush());
+        Ok(Self { _file: file })
+    }
+}
+
+/// Lock every directory once, in the given order
+pub fn lock_dirs(dirs: &[String], wait: Option<Duration>) -> Result<Vec<DirLock>, AliasError> {
+    let mut locked: Vec<PathBuf> = Vec::new();
+    let mut locks = Vec::new();
+    for dir in dirs {
+        let dir = Path::new(dir);
+        let _ = fs::create_dir_all(dir);
+        let canonical_dir = fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+        if locked.contains(&canonical_dir) {
+            continue;
+        }
+        locks.push(DirLock::acquire(dir, wait)?);
+        locked.push(canonical_dir);
+    }
+    Ok(locks)
+}
+
+/// PID written by the lock holder, it can not be read on platforms with mandatory locks
+fn read_holder(path: &Path) -> String {
+    let mut holder = String::new();
+    match File::open(path).and_then(|mut f| f.read_to_string(&mut holder)) {
+        Ok(_) if !holder.trim().is_empty() => holder.trim().to_owned(),
+        _ => "unknown".to_owned(),
+    }
+}
@@ -0,0 +1,77 @@
+use super::error::{AliasError, ErrorKind};
+
+/// Setting version written by this binary
+pub const SETTING_VERSION: u32 = 1;
+
+/// Upgrade a setting by one version, `MIGRATIONS[n]` upgrades version `n` to `n + 1`
+type Migration = fn(&mut toml::Table);
+
+const MIGRATIONS: [Migration; SETTING_VERSION as usize] = [migrate_v0_to_v1];
+
+/// Version of a setting, a setting without `version` key is version 0
+pub fn get_version(setting: &toml::Table, setting_path: &str) -> Result<u32, AliasError> {
+    match setting.get("version") {
+        None => Ok(0),
+        Some(version) => version
+            .as_integer()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| AliasError {
+                kind: ErrorKind::Unkonw,
+                msg: format!(
+                    "setting version should be a non-negative integer :: {} :: {}",
+                    setting_path, version
+                ),
+            }),
+    }
+}
+
+/// Upgrade a setting to `SETTING_VERSION` in memory, return the version before migration
+pub fn migrate(setting: &mut toml::Table, setting_path: &str) -> Result<u32, AliasError> {
+    let version = get_version(setting, setting_path)?;
+    if version > SETTING_VERSION {
+        return Err(AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!(
+                "setting version is newer than this alias-rs supports, please upgrade alias-rs :: {} :: version={}, supported={}",
+                setting_path, version, SETTING_VERSION
+            ),
+        });
+    }
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(setting);
+    }
+    // keep `version` as the first key
+    let mut migrated = toml::Table::new();
+    migrated.insert(
+        "version".to_owned(),
+        toml::Value::Integer(SETTING_VERSION as i64),
+    );
+    migrated.extend(
+        std::mem::take(setting)
+            .into_iter()
+            .filter(|(k, _)| k != "version"),
+    );
+    *setting = migrated;
+    Ok(version)
+}
+
+/// Version 0 is the setting before `version` key was added, same shape as version 1
+fn migrate_v0_to_v1(_setting: &mut toml::Table) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version_of(content: &str) -> Result<u32, AliasError> {
+        get_version(&toml::from_str(content).unwrap(), "setting.toml")
+    }
+
+    #[test]
+    fn version_is_a_u32() {
+        assert_eq!(version_of("").unwrap(), 0);
+        assert_eq!(version_of("version = 1").unwrap(), 1);
+        for invalid in ["version = -1", "version = 4294967296", "version = \"1\""] {
+            assert!(version_of(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
@@ -1,4 +1,16 @@
 pub mod alias;
+pub mod alias_history;
 pub mod alias_import;
+pub mod alias_search;
 pub mod alias_setting;
+pub mod backup;
 pub mod error;
+pub mod journal;
+pub mod lock;
+pub mod migration;
+pub mod setting_check;
+pub mod setting_document;
+pub mod setting_format;
+pub mod setting_index;
+pub mod transaction;
+pub mod variable;
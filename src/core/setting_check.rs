@@ -0,0 +1,327 @@
+use super::{
+    alias::{is_valid_alias_name, to_valid_alias_name},
+    alias_setting::{self, AliasSetting},
+    migration::{self, SETTING_VERSION},
+    setting_format::{position, ParseError, SettingFormat},
+    variable::{RuntimeVariables, Variable},
+};
+use regex::Regex;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub path: String,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+    pub help: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(
+            f,
+            "{}: {}\n  --> {}:{}:{}\n  help: {}",
+            severity, self.message, self.path, self.line, self.column, self.help
+        )
+    }
+}
+
+struct CheckedFile {
+    path: String,
+    content: String,
+    setting: AliasSetting,
+}
+
+/// Check a setting file and the files it includes, report every issue found
+pub fn check(setting_path: &String, runtime_variables: &RuntimeVariables) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut files = Vec::new();
+    let mut visited = HashSet::new();
+    if !Path::new(setting_path).is_file() {
+        diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            path: setting_path.to_owned(),
+            line: 1,
+            column: 1,
+            message: alias_setting::not_found_message(setting_path),
+            help: "check the setting path".to_owned(),
+        });
+        return diagnostics;
+    }
+    collect(setting_path, &mut visited, &mut files, &mut diagnostics);
+    // variables of all files, used by every file
+    let resolved_variables = runtime_variables.resolve(
+        files
+            .iter()
+            .map(|f| (f.path.clone(), f.setting.variables.clone()))
+            .collect(),
+    );
+    let mut defined_aliases: HashMap<String, (String, usize, usize)> = HashMap::new();
+    for file in &files {
+        // a project setting or an included file may not set `script`, see `alias_setting::load`
+        let is_project = Path::new(&file.path).file_name().is_some_and(|name| {
+            alias_setting::PROJECT_SETTING_NAMES.contains(&&*name.to_string_lossy())
+        });
+        check_script(
+            file,
+            file.path != *setting_path || is_project,
+            &mut diagnostics,
+        );
+        check_placeholders(file, &resolved_variables, &mut diagnostics);
+        for alias in file.setting.aliases.keys() {
+            let (line, column) = locate_key(&file.content, alias);
+            if !is_valid_alias_name(alias) {
+                diagnostics.push(Diagnostic {
+                    severity: Severity::Error,
+                    path: file.path.clone(),
+                    line,
+                    column,
+                    message: format!("invalid alias name `{}`", alias),
+                    help: format!(
+                        "use only letters, digits, '_', '-' and '.', e.g. `{}`",
+                        to_valid_alias_name(alias)
+                    ),
+                });
+            }
+            if file.setting.aliases[alias].command().trim().is_empty() {
+                diagnostics.push(Diagnostic {
+                    severity: Severity::Error,
+                    path: file.path.clone(),
+                    line,
+                    column,
+                    message: format!("alias `{}` command is empty", alias),
+                    help: "set the command, or remove the alias".to_owned(),
+                });
+            }
+            if let Some((path, defined_line, defined_column)) = defined_aliases.get(alias) {
+                diagnostics.push(Diagnostic {
+                    severity: Severity::Warning,
+                    path: file.path.clone(),
+                    line,
+                    column,
+                    message: format!(
+                        "alias `{}` is also defined in {}:{}:{}, this one takes precedence",
+                        alias, path, defined_line, defined_column
+                    ),
+                    help: "remove one of them if the override is not intended".to_owned(),
+                });
+            }
+            defined_aliases.insert(alias.clone(), (file.path.clone(), line, column));
+        }
+    }
+    diagnostics
+}
+
+/// Read and parse a setting file, after the files it includes
+fn collect(
+    path: &str,
+    visited: &mut HashSet<PathBuf>,
+    files: &mut Vec<CheckedFile>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let canonical_path = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+    if !visited.insert(canonical_path) {
+        return;
+    }
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                path: path.to_owned(),
+                line: 1,
+                column: 1,
+                message: format!("read setting fail :: {}", e),
+                help: "check the file permission and encoding (UTF-8)".to_owned(),
+            });
+            return;
+        }
+    };
+    let format = SettingFormat::from_path(path);
+    let table = match format.parse_document(&content) {
+        Ok(table) => table,
+        Err(e) => {
+            diagnostics.push(parse_error(path, &e));
+            return;
+        }
+    };
+    match migration::get_version(&table, path) {
+        Ok(version) if version > SETTING_VERSION => {
+            let (line, column) = locate_key(&content, "version");
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                path: path.to_owned(),
+                line,
+                column,
+                message: format!(
+                    "setting version {} is newer than supported version {}",
+                    version, SETTING_VERSION
+                ),
+                help: "upgrade alias-rs".to_owned(),
+            });
+            return;
+        }
+        Ok(_) => {}
+        Err(e) => {
+            let (line, column) = locate_key(&content, "version");
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                path: path.to_owned(),
+                line,
+                column,
+                message: e.msg,
+                help: format!("set `version = {}`", SETTING_VERSION),
+            });
+            return;
+        }
+    }
+    let setting = match format.parse::<AliasSetting>(&content) {
+        Ok(setting) => setting,
+        Err(e) => {
+            diagnostics.push(parse_error(path, &e));
+            return;
+        }
+    };
+    let base_dir = Path::new(path).parent().unwrap_or(Path::new(""));
+    for include in &setting.include {
+        let include_path = base_dir.join(include);
+        if include_path.is_file() {
+            collect(&include_path.to_string_lossy(), visited, files, diagnostics);
+        } else {
+            let (line, column) = locate(&content, &format!("\"{}\"", include))
+                .or_else(|| locate(&content, &format!("'{}'", include)))
+                .or_else(|| locate(&content, include))
+                .unwrap_or((1, 1));
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                path: path.to_owned(),
+                line,
+                column,
+                message: format!("include not exists :: {}", include_path.display()),
+                help: "include path is relative to the including setting file".to_owned(),
+            });
+        }
+    }
+    files.push(CheckedFile {
+        path: path.to_owned(),
+        content,
+        setting,
+    });
+}
+
+fn check_script(file: &CheckedFile, restricted: bool, diagnostics: &mut Vec<Diagnostic>) {
+    let script = &file.setting.script;
+    if restricted
+        && (script.home.is_some() || script.home_env_name.is_some() || script.mode.is_some())
+    {
+        let (line, column) = locate(&file.content, "script").unwrap_or((1, 1));
+        diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            path: file.path.clone(),
+            line,
+            column,
+            message: "`script` is not allowed in a project or included setting".to_owned(),
+            help: "move `script` to the user setting".to_owned(),
+        });
+    }
+    for (key, value, help) in [
+        (
+            "home",
+            &script.home,
+            "remove `home` to use the default script home, or set it to a directory",
+        ),
+        (
+            "home_env_name",
+            &script.home_env_name,
+            "remove `home_env_name` to use the default name, or set it to an environment variable name",
+        ),
+    ] {
+        if value.as_ref().is_some_and(|v| v.trim().is_empty()) {
+            let (line, column) = locate_key(&file.content, key);
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                path: file.path.clone(),
+                line,
+                column,
+                message: format!("script {} is empty", key),
+                help: help.to_owned(),
+            });
+        }
+    }
+}
+
+fn check_placeholders(
+    file: &CheckedFile,
+    resolved_variables: &BTreeMap<String, Variable>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let regex = Regex::new(r"\{\{([^{}]*)\}\}").unwrap();
+    for captures in regex.captures_iter(&file.content) {
+        let placeholder = captures.get(0).unwrap();
+        let name = &captures[1];
+        if resolved_variables.contains_key(name) {
+            continue;
+        }
+        let (line, column) = position(&file.content, placeholder.start());
+        let line_content = file.content.lines().nth(line - 1).unwrap_or_default();
+        if line_content.trim_start().starts_with('#') {
+            continue;
+        }
+        diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            path: file.path.clone(),
+            line,
+            column,
+            message: format!("unresolved placeholder `{}`", placeholder.as_str()),
+            help: format!(
+                "define `{}` in [variables], or pass --define {}=<value>",
+                name, name
+            ),
+        });
+    }
+}
+
+fn parse_error(path: &str, e: &ParseError) -> Diagnostic {
+    let (line, column) = e.position.unwrap_or((1, 1));
+    Diagnostic {
+        severity: Severity::Error,
+        path: path.to_owned(),
+        line,
+        column,
+        message: e.message.clone(),
+        help: "fix the setting syntax, see template/alias-setting.toml".to_owned(),
+    }
+}
+
+/// Line and column (1-based) of a key definition, e.g. `key = ...`, `key: ...` or `"key": ...`
+fn locate_key(content: &str, key: &str) -> (usize, usize) {
+    let regex = Regex::new(&format!(
+        r#"(?m)^[ \t]*(["']?{}["']?[ \t]*[=:])"#,
+        regex::escape(key)
+    ))
+    .unwrap();
+    regex
+        .captures(content)
+        .map_or((1, 1), |c| position(content, c.get(1).unwrap().start()))
+}
+
+fn locate(content: &str, needle: &str) -> Option<(usize, usize)> {
+    content.find(needle).map(|offset| position(content, offset))
+}
@@ -0,0 +1,186 @@
+use super::setting_format::{ParseError, SettingFormat};
+use toml_edit::DocumentMut;
+
+/// A setting file opened for editing its aliases.
+///
+/// TOML is edited in place so its comments and layout are kept, YAML and JSON are rewritten.
+pub enum SettingDocument {
+    Toml(DocumentMut),
+    Other(SettingFormat, toml::Table),
+}
+
+impl SettingDocument {
+    pub fn parse(format: SettingFormat, content: &str) -> Result<Self, ParseError> {
+        match format {
+            SettingFormat::Toml => {
+                // parse with toml first for the same error messages as loading
+                format.parse_document(content)?;
+                content
+                    .parse::<DocumentMut>()
+                    .map(SettingDocument::Toml)
+                    .map_err(|e| ParseError {
+                        message: e.message().to_owned(),
+                        position: None,
+                    })
+            }
+            _ => Ok(SettingDocument::Other(
+                format,
+                format.parse_document(content)?,
+            )),
+        }
+    }
+
+    /// Command of an alias as written, with its placeholders, `None` if absent
+    pub fn alias_command(&self, alias: &str) -> Option<String> {
+        match self {
+            SettingDocument::Toml(doc) => {
+                let item = doc.get("aliases")?.as_table_like()?.get(alias)?;
+                match item.as_str() {
+                    Some(command) => Some(command.to_owned()),
+                    None => item
+                        .as_table_like()?
+                        .get("command")?
+                        .as_str()
+                        .map(str::to_owned),
+                }
+            }
+            SettingDocument::Other(_, table) => {
+                let value = table.get("aliases")?.as_table()?.get(alias)?;
+                match value.as_str() {
+                    Some(command) => Some(command.to_owned()),
+                    None => value.get("command")?.as_str().map(str::to_owned),
+                }
+            }
+        }
+    }
+
+    /// Set the command of an alias, keeping its metadata if any
+    pub fn set_alias_command(&mut self, alias: &str, command: &str) {
+        match self {
+            SettingDocument::Toml(doc) => {
+                let aliases = doc
+                    .entry("aliases")
+                    .or_insert(toml_edit::table())
+                    .as_table_like_mut()
+                    .unwrap();
+                match aliases
+                    .get_mut(alias)
+                    .and_then(|item| item.as_table_like_mut())
+                {
+                    Some(detail) => {
+                        detail.insert("command", toml_edit::value(command));
+                    }
+                    None => {
+                        aliases.insert(alias, toml_edit::value(command));
+                    }
+                }
+            }
+            SettingDocument::Other(_, table) => {
+                let aliases = get_aliases_mut(table);
+                match aliases.get_mut(alias).and_then(|v| v.as_table_mut()) {
+                    Some(detail) => {
+                        detail.insert("command".to_owned(), command.into());
+                    }
+                    None => {
+                        aliases.insert(alias.to_owned(), command.into());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Copy an alias define as it is, with its metadata and comments, return false if absent
+    pub fn copy_alias(&mut self, source: &str, target: &str) -> bool {
+        match self {
+            SettingDocument::Toml(doc) => {
+                let Some(aliases) = doc
+                    .get_mut("aliases")
+                    .and_then(|item| item.as_table_like_mut())
+                else {
+                    return false;
+                };
+                let Some(item) = aliases.get(source).cloned() else {
+                    return false;
+                };
+                aliases.insert(target, item);
+                true
+            }
+            SettingDocument::Other(_, table) => {
+                let aliases = get_aliases_mut(table);
+                let Some(value) = aliases.get(source).cloned() else {
+                    return false;
+                };
+                aliases.insert(target.to_owned(), value);
+                true
+            }
+        }
+    }
+
+    /// Set an alias define, replacing the present one
+    pub fn set_alias(&mut self, alias: &str, define: toml::Value) {
+        match self {
+            SettingDocument::Toml(doc) => {
+                let item = value_to_item(alias, define);
+                doc.entry("aliases")
+                    .or_insert(toml_edit::table())
+                    .as_table_like_mut()
+                    .unwrap()
+                    .insert(alias, item);
+            }
+            SettingDocument::Other(_, table) => {
+                get_aliases_mut(table).insert(alias.to_owned(), define);
+            }
+        }
+    }
+
+    /// Remove an alias, return its define if it was present
+    pub fn remove_alias(&mut self, alias: &str) -> Option<toml::Value> {
+        match self {
+            SettingDocument::Toml(doc) => doc
+                .get_mut("aliases")?
+                .as_table_like_mut()?
+                .remove(alias)
+                .and_then(|item| item_to_value(&item)),
+            SettingDocument::Other(_, table) => {
+                table.get_mut("aliases")?.as_table_mut()?.remove(alias)
+            }
+        }
+    }
+
+    pub fn serialize(&self) -> Result<String, String> {
+        match self {
+            SettingDocument::Toml(doc) => Ok(doc.to_string()),
+            SettingDocument::Other(format, table) => format.serialize(table),
+        }
+    }
+}
+
+fn get_aliases_mut(table: &mut toml::Table) -> &mut toml::Table {
+    if !table.get("aliases").is_some_and(|v| v.is_table()) {
+        table.insert("aliases".to_owned(), toml::Table::new().into());
+    }
+    table.get_mut("aliases").unwrap().as_table_mut().unwrap()
+}
+
+/// Convert through a one key document, a table define becomes a `[aliases.<alias>]` table
+fn value_to_item(key: &str, value: toml::Value) -> toml_edit::Item {
+    let mut table = toml::Table::new();
+    table.insert(key.to_owned(), value.clone());
+    toml::to_string(&table)
+        .ok()
+        .and_then(|s| s.parse::<DocumentMut>().ok())
+        .and_then(|mut doc| doc.remove(key))
+        .unwrap_or_else(|| match value {
+            toml::Value::String(s) => toml_edit::value(s),
+            _ => toml_edit::Item::None,
+        })
+}
+
+/// Convert through a one key document, `v = <item>`
+fn item_to_value(item: &toml_edit::Item) -> Option<toml::Value> {
+    let mut doc = DocumentMut::new();
+    doc.insert("v", item.clone());
+    toml::from_str::<toml::Table>(&doc.to_string())
+        .ok()?
+        .remove("v")
+}
@@ -0,0 +1,114 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{fmt::Display, path::Path};
+
+/// Setting file format, detected from the file extension
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SettingFormat {
+    Toml,
+    Yaml,
+    Json,
+}
+
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    /// error message, including the position if known
+    pub message: String,
+    /// line and column (1-based), if known
+    pub position: Option<(usize, usize)>,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl SettingFormat {
+    /// `.yaml`/`.yml` is YAML, `.json` is JSON, others are TOML
+    pub fn from_path(path: &str) -> Self {
+        let extension = Path::new(path)
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("yaml") | Some("yml") => SettingFormat::Yaml,
+            Some("json") => SettingFormat::Json,
+            _ => SettingFormat::Toml,
+        }
+    }
+
+    pub fn parse<T: DeserializeOwned>(&self, content: &str) -> Result<T, ParseError> {
+        match self {
+            SettingFormat::Toml => toml::from_str(content).map_err(|e| {
+                let position = e.span().map(|span| position(content, span.start));
+                let message = e.message().trim().replace('\n', ", ");
+                ParseError {
+                    message: match position {
+                        Some((line, column)) => {
+                            format!("{} at line {} column {}", message, line, column)
+                        }
+                        None => message,
+                    },
+                    position,
+                }
+            }),
+            SettingFormat::Yaml => serde_norway::from_str(content).map_err(|e| ParseError {
+                position: e.location().map(|l| (l.line(), l.column())),
+                message: e.to_string(),
+            }),
+            SettingFormat::Json => serde_json::from_str(content).map_err(|e| ParseError {
+                position: Some((e.line(), e.column())),
+                message: e.to_string(),
+            }),
+        }
+    }
+
+    /// Parse a setting as a document, `null` values of YAML and JSON are taken as absent keys
+    pub fn parse_document(&self, content: &str) -> Result<toml::Table, ParseError> {
+        match self {
+            SettingFormat::Toml => self.parse(content),
+            SettingFormat::Yaml | SettingFormat::Json => {
+                let mut value: serde_json::Value = self.parse(content)?;
+                remove_null(&mut value);
+                toml::Table::deserialize(value).map_err(|e| ParseError {
+                    message: e.to_string(),
+                    position: None,
+                })
+            }
+        }
+    }
+
+    pub fn serialize<T: Serialize>(self, value: &T) -> Result<String, String> {
+        match self {
+            SettingFormat::Toml => toml::to_string_pretty(value).map_err(|e| e.to_string()),
+            SettingFormat::Yaml => serde_norway::to_string(value).map_err(|e| e.to_string()),
+            SettingFormat::Json => serde_json::to_string_pretty(value)
+                .map(|s| s + "\n")
+                .map_err(|e| e.to_string()),
+        }
+    }
+}
+
+fn remove_null(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            map.retain(|_, v| !v.is_null());
+            map.values_mut().for_each(remove_null);
+        }
+        serde_json::Value::Array(array) => array.iter_mut().for_each(remove_null),
+        _ => {}
+    }
+}
+
+/// Line and column (1-based) of a byte offset
+pub fn position(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rsplit('\n')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .count()
+        + 1;
+    (line, column)
+}
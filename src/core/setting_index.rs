@@ -0,0 +1,219 @@
+use super::{
+    alias_setting::{self, AliasKind, AliasSetting},
+    error::{AliasError, ErrorKind},
+    variable::RuntimeVariables,
+};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{fs, path::Path, process};
+
+const INDEX_FILE_NAME: &str = "setting-index.bin";
+/// Hidden, so it is not listed as an alias
+const SCRIPT_HOME_INDEX_NAME: &str = ".alias-rs-index.bin";
+const INDEX_VERSION: u32 = 3;
+
+/// Loaded setting precompiled into a compact binary file beside the user setting,
+/// so reading commands (e.g. `list`, `show`, `run`) skip parsing and merging the setting files.
+/// A copy of the system and user setting only is kept in the script home, where multicall
+/// links look up their commands
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SettingIndex {
+    pub version: u32,
+    /// user setting path
+    pub setting_path: String,
+    /// setting files loaded, in load order
+    pub files: Vec<String>,
+    /// project setting loaded, it depends on the current directory
+    pub project_setting: Option<String>,
+    /// hash of the setting files and the runtime variables, see [`hash`]
+    pub hash: String,
+    pub script_home: String,
+    /// values of the resolved variables
+    pub variables: IndexMap<String, String>,
+    pub aliases: IndexMap<String, IndexedAlias>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IndexedAlias {
+    /// command with the placeholders replaced
+    pub command: String,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    pub kind: AliasKind,
+    /// setting file the alias came from, with its layer
+    pub source: String,
+}
+
+/// Hash of the setting files, as `(path, content)` in load order, and the runtime variables
+pub fn hash<'a>(
+    files: impl Iterator<Item = (&'a str, &'a [u8])>,
+    runtime_variables: &RuntimeVariables,
+) -> String {
+    let mut hasher = Sha256::new();
+    for (path, content) in files {
+        hasher.update(path.as_bytes());
+        hasher.update((content.len() as u64).to_le_bytes());
+        hasher.update(content);
+    }
+    hasher.update(runtime_variables.fingerprint().as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+fn index_path(setting_path: &str) -> String {
+    Path::new(&alias_setting::get_setting_dir(setting_path))
+        .join(INDEX_FILE_NAME)
+        .to_string_lossy()
+        .to_string()
+}
+
+impl SettingIndex {
+    pub fn new(setting: &AliasSetting, setting_path: &str) -> Self {
+        let aliases = setting
+            .aliases
+            .iter()
+            .map(|(alias, define)| {
+                let indexed = IndexedAlias {
+                    command: define.command().clone(),
+                    description: define.description().cloned(),
+                    tags: define.tags().to_vec(),
+                    kind: define.kind(),
+                    source: setting
+                        .alias_sources
+                        .get(alias)
+                        .map_or(String::default(), |s| s.to_string()),
+                };
+                (alias.clone(), indexed)
+            })
+            .collect();
+        Self {
+            version: INDEX_VERSION,
+            setting_path: setting_path.to_owned(),
+            files: setting.files.clone(),
+            project_setting: setting.project_setting.clone(),
+            hash: setting.hash.clone(),
+            script_home: setting.script.home.clone().unwrap_or_default(),
+            variables: setting
+                .resolved_variables
+                .iter()
+                .map(|(name, variable)| (name.clone(), variable.value.clone()))
+                .collect(),
+            aliases,
+        }
+    }
+
+    /// The index beside the user setting, `None` if absent, unreadable or stale.
+    /// It is stale if a setting file was changed, added (e.g. a project setting) or removed,
+    /// or the runtime variables differ
+    pub fn load(
+        system_setting_path: &str,
+        setting_path: &str,
+        runtime_variables: &RuntimeVariables,
+    ) -> Option<Self> {
+        let content = fs::read(index_path(setting_path)).ok()?;
+        let index: Self = bincode::deserialize(&content).ok()?;
+        (index.version == INDEX_VERSION
+            && index.setting_path == setting_path
+            && index.project_setting == alias_setting::find_project_setting()
+            && index.is_current(system_setting_path, runtime_variables))
+        .then_some(index)
+    }
+
+    /// The index copy in a script home, `None` if it has none. It is built without the project
+    /// setting and the runtime variables, as a multicall link may run from any directory.
+    /// If a setting file was changed since, e.g. by hand, the setting is loaded instead
+    pub fn load_script_home(
+        script_home: &str,
+        system_setting_path: &str,
+    ) -> Result<Option<Self>, AliasError> {
+        let path = Path::new(script_home).join(SCRIPT_HOME_INDEX_NAME);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let index = fs::read(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| bincode::deserialize::<Self>(&content).map_err(|e| e.to_string()))
+            .and_then(|index| match index.version {
+                INDEX_VERSION => Ok(index),
+                version => Err(format!("version {}", version)),
+            })
+            .map_err(|e| AliasError {
+                kind: ErrorKind::Unkonw,
+                msg: format!(
+                    "read alias index fail, run `alias-rs import` to rebuild it :: {} :: {}",
+                    path.display(),
+                    e
+                ),
+            })?;
+        let runtime_variables = RuntimeVariables::default();
+        if index.is_current(system_setting_path, &runtime_variables) {
+            return Ok(Some(index));
+        }
+        let mut setting = alias_setting::load_without_project(
+            system_setting_path,
+            &index.setting_path,
+            &runtime_variables,
+        )?;
+        setting.script.home = Some(index.script_home);
+        Ok(Some(Self::new(&setting, &index.setting_path)))
+    }
+
+    /// Whether the index was built from the setting files as they are now. It is not if a file
+    /// was changed, added (e.g. a system setting) or removed, or the runtime variables differ
+    fn is_current(&self, system_setting_path: &str, runtime_variables: &RuntimeVariables) -> bool {
+        let system_setting = Path::new(system_setting_path)
+            .is_file()
+            .then(|| system_setting_path.to_owned());
+        let layers = [system_setting, Some(self.setting_path.clone())];
+        if !layers
+            .iter()
+            .flatten()
+            .all(|path| self.files.contains(path))
+        {
+            return false;
+        }
+        let Some(contents) = self
+            .files
+            .iter()
+            .map(|path| fs::read(path).ok().map(|content| (path.as_str(), content)))
+            .collect::<Option<Vec<_>>>()
+        else {
+            return false;
+        };
+        let current = hash(
+            contents
+                .iter()
+                .map(|(path, content)| (*path, content.as_slice())),
+            runtime_variables,
+        );
+        current == self.hash
+    }
+
+    /// Write the index beside the user setting, replacing the old one at once
+    pub fn write(&self, setting_path: &str) -> Result<(), AliasError> {
+        self.write_file(&index_path(setting_path))
+    }
+
+    /// Write the index copy in the script home, after the scripts are changed
+    pub fn write_script_home(&self) -> Result<(), AliasError> {
+        let path = Path::new(&self.script_home).join(SCRIPT_HOME_INDEX_NAME);
+        self.write_file(&path.to_string_lossy())
+    }
+
+    fn write_file(&self, path: &str) -> Result<(), AliasError> {
+        let temp_path = format!("{}.{}.tmp", path, process::id());
+        let content = bincode::serialize(self).map_err(|e| AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!("serialize setting index fail :: {}", e),
+        })?;
+        fs::write(&temp_path, content)
+            .and_then(|_| fs::rename(&temp_path, path))
+            .map_err(|e| {
+                let _ = fs::remove_file(&temp_path);
+                AliasError {
+                    kind: ErrorKind::Unkonw,
+                    msg: format!("write setting index fail :: {} :: {}", path, e),
+                }
+            })
+    }
+}
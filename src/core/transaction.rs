@@ -0,0 +1,533 @@
+use super::{
+    error::{AliasError, ErrorKind},
+    journal::{Changes, Content, EnvRecord, FileRecord},
+};
+use crate::cmn::{files, windows_like};
+use std::{collections::HashMap, fs, path::PathBuf, process};
+
+/// A file, or a symbolic link to a file
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileState {
+    Content(Vec<u8>),
+    Link(String),
+}
+
+impl FileState {
+    /// State of a file on disk, links are not followed, `None` if absent
+    pub fn read(path: &str) -> Result<Option<Self>, AliasError> {
+        let read_error = |e: std::io::Error| AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!("read file fail :: {} :: {}", path, e),
+        };
+        match fs::symlink_metadata(path) {
+            Err(_) => Ok(None),
+            Ok(metadata) if metadata.is_symlink() => fs::read_link(path)
+                .map(|target| Some(FileState::Link(target.to_string_lossy().to_string())))
+                .map_err(read_error),
+            Ok(_) => fs::read(path)
+                .map(|content| Some(FileState::Content(content)))
+                .map_err(read_error),
+        }
+    }
+}
+
+/// A file change staged in a transaction, `state` is `None` for a removal
+#[derive(Debug, Clone)]
+pub struct FileChange {
+    pub path: String,
+    pub state: Option<FileState>,
+}
+
+/// A user environment variable change staged in a transaction (Windows), `value` is `None` to unset
+#[derive(Debug, Clone)]
+pub struct EnvChange {
+    pub name: String,
+    pub before: Option<String>,
+    pub value: Option<String>,
+}
+
+/// File writes, links and removals applied all together, or not at all.
+///
+/// Changes are staged in memory, then on commit every new content is written to a temp dir
+/// beside its target, and only after all writes succeed the targets are replaced by renames.
+/// Replaced files are moved aside first, so a failure on any rename restores all of them.
+/// Contents are read and written through symbolic links, and keep the mode of the file replaced.
+/// User environment variables are set after the files, and restored too on failure.
+#[derive(Debug, Clone, Default)]
+pub struct Transaction {
+    changes: Vec<FileChange>,
+    env_changes: Vec<EnvChange>,
+}
+
+/// A change with its new content written beside the target
+struct StagedChange {
+    target: PathBuf,
+    new_file: Option<PathBuf>,
+    backup: PathBuf,
+    record: FileRecord,
+}
+
+/// A change done on commit, kept to undo it on rollback
+enum Applied {
+    /// target moved aside to backup
+    MovedAside { target: PathBuf, backup: PathBuf },
+    /// staged file moved to target
+    Placed { target: PathBuf },
+    /// user environment variable set, with its value before
+    EnvSet {
+        name: String,
+        before: Option<String>,
+    },
+}
+
+impl Transaction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write the content of `path`, or of the file it links to
+    pub fn write(&mut self, path: String, content: Vec<u8>) {
+        let path = self.resolve(path);
+        self.stage(path, Some(FileState::Content(content)));
+    }
+
+    /// Make `path` a symbolic link to `target`
+    pub fn link(&mut self, path: String, target: String) {
+        self.stage(path, Some(FileState::Link(target)));
+    }
+
+    pub fn remove(&mut self, path: String) {
+        self.stage(path, None);
+    }
+
+    /// Set a user environment variable, `before` is its current value
+    pub fn set_user_env(&mut self, name: String, before: Option<String>, value: Option<String>) {
+        self.env_changes.retain(|c| c.name != name);
+        self.env_changes.push(EnvChange {
+            name,
+            before,
+            value,
+        });
+    }
+
+    /// The file a link on disk points to, a path staged in this transaction is kept as it is
+    fn resolve(&self, path: String) -> String {
+        if self.changes.iter().any(|c| c.path == path) {
+            return path;
+        }
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.is_symlink() => fs::canonicalize(&path)
+                .map(|target| target.to_string_lossy().to_string())
+                .unwrap_or(path),
+            _ => path,
+        }
+    }
+
+    fn stage(&mut self, path: String, state: Option<FileState>) {
+        // a later change of the same file replaces the earlier one
+        self.changes.retain(|c| c.path != path);
+        self.changes.push(FileChange { path, state });
+    }
+
+    /// State of a file as the transaction would leave it, `None` if absent
+    pub fn state(&self, path: &str) -> Result<Option<FileState>, AliasError> {
+        match self.changes.iter().find(|c| c.path == path) {
+            Some(change) => Ok(change.state.clone()),
+            None => FileState::read(path),
+        }
+    }
+
+    /// Content of a file, or of the file it links to, as the transaction would leave it,
+    /// `None` if absent or a broken link
+    pub fn read(&self, path: &str) -> Result<Option<Vec<u8>>, AliasError> {
+        Ok(match self.state(&self.resolve(path.to_owned()))? {
+            Some(FileState::Content(content)) => Some(content),
+            _ => None,
+        })
+    }
+
+    /// Apply all staged changes, return what they changed for the journal
+    pub fn commit(self) -> Result<Changes, AliasError> {
+        let mut temp_dirs: HashMap<PathBuf, PathBuf> = HashMap::new();
+        let result = self.prepare(&mut temp_dirs).and_then(|staged| {
+            let mut applied = Vec::new();
+            let result = Self::apply(&staged, &mut applied)
+                .and_then(|_| Self::apply_envs(&self.env_changes, &mut applied));
+            if result.is_err() {
+                Self::rollback(applied);
+            }
+            result.map(|_| Changes {
+                files: staged.into_iter().map(|s| s.record).collect(),
+                envs: self
+                    .env_changes
+                    .iter()
+                    .filter(|c| c.before != c.value)
+                    .map(|c| EnvRecord {
+                        name: c.name.clone(),
+                        before: c.before.clone(),
+                        after: c.value.clone(),
+                    })
+                    .collect(),
+            })
+        });
+        for temp_dir in temp_dirs.values() {
+            let _ = fs::remove_dir_all(temp_dir);
+        }
+        result
+    }
+
+    /// Write every new content to the temp dir beside its target
+    fn prepare(
+        &self,
+        temp_dirs: &mut HashMap<PathBuf, PathBuf>,
+    ) -> Result<Vec<StagedChange>, AliasError> {
+        let mut staged = Vec::new();
+        for (index, change) in self.changes.iter().enumerate() {
+            let target = PathBuf::from(&change.path);
+            let parent = match target.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+                _ => PathBuf::from("."),
+            };
+            let before = FileState::read(&change.path)?;
+            if before == change.state {
+                continue;
+            }
+            if !temp_dirs.contains_key(&parent) {
+                let temp_dir = parent.join(format!(".alias-rs-tx-{}", process::id()));
+                fs::create_dir_all(&temp_dir).map_err(|e| AliasError {
+                    kind: ErrorKind::Unkonw,
+                    msg: format!(
+                        "create transaction dir fail :: {} :: {}",
+                        temp_dir.display(),
+                        e
+                    ),
+                })?;
+                temp_dirs.insert(parent.clone(), temp_dir);
+            }
+            let temp_dir = &temp_dirs[&parent];
+            let new_file = match &change.state {
+                Some(state) => {
+                    let new_file = temp_dir.join(format!("{}.new", index));
+                    match state {
+                        FileState::Content(content) => {
+                            fs::write(&new_file, content).and_then(|_| {
+                                // keep the mode of the file replaced, e.g. an executable script
+                                match &before {
+                                    Some(FileState::Content(_)) => fs::set_permissions(
+                                        &new_file,
+                                        fs::metadata(&target)?.permissions(),
+                                    ),
+                                    _ => Ok(()),
+                                }
+                            })
+                        }
+                        FileState::Link(link_target) => files::symlink(link_target, &new_file),
+                    }
+                    .map_err(|e| AliasError {
+                        kind: ErrorKind::Unkonw,
+                        msg: format!("stage file fail :: {} :: {}", change.path, e),
+                    })?;
+                    Some(new_file)
+                }
+                None => None,
+            };
+            staged.push(StagedChange {
+                target,
+                new_file,
+                backup: temp_dir.join(format!("{}.old", index)),
+                record: FileRecord {
+                    path: change.path.clone(),
+                    before: before.map(Content::from),
+                    after: change.state.clone().map(Content::from),
+                },
+            });
+        }
+        Ok(staged)
+    }
+
+    fn apply(staged: &[StagedChange], applied: &mut Vec<Applied>) -> Result<(), AliasError> {
+        for StagedChange {
+            target,
+            new_file,
+            backup,
+            ..
+        } in staged
+        {
+            // a link is moved itself, even if it is broken
+            if fs::symlink_metadata(target).is_ok() {
+                fs::rename(target, backup).map_err(|e| AliasError {
+                    kind: ErrorKind::Unkonw,
+                    msg: format!("replace file fail :: {} :: {}", target.display(), e),
+                })?;
+                applied.push(Applied::MovedAside {
+                    target: target.clone(),
+                    backup: backup.clone(),
+                });
+            }
+            if let Some(new_file) = new_file {
+                fs::rename(new_file, target).map_err(|e| AliasError {
+                    kind: ErrorKind::Unkonw,
+                    msg: format!("write file fail :: {} :: {}", target.display(), e),
+                })?;
+                applied.push(Applied::Placed {
+                    target: target.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_envs(env_changes: &[EnvChange], applied: &mut Vec<Applied>) -> Result<(), AliasError> {
+        for change in env_changes.iter().filter(|c| c.before != c.value) {
+            // an empty value removes the variable
+            windows_like::set_user_env_var(
+                change.name.clone(),
+                change.value.clone().unwrap_or_default(),
+            )?;
+            applied.push(Applied::EnvSet {
+                name: change.name.clone(),
+                before: change.before.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    fn rollback(applied: Vec<Applied>) {
+        for applied in applied.into_iter().rev() {
+            match applied {
+                Applied::Placed { target } => {
+                    let _ = fs::remove_file(target);
+                }
+                Applied::MovedAside { target, backup } => {
+                    let _ = fs::rename(backup, target);
+                }
+                Applied::EnvSet { name, before } => {
+                    let _ = windows_like::set_user_env_var(name, before.unwrap_or_default());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    /// An empty dir of its own for each test
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("alias-rs-tx-test-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn path(dir: &Path, name: &str) -> String {
+        dir.join(name).to_string_lossy().to_string()
+    }
+
+    fn content(path: &str) -> Option<FileState> {
+        FileState::read(path).unwrap()
+    }
+
+    fn no_temp_dir(dir: &Path) -> bool {
+        fs::read_dir(dir).unwrap().all(|e| {
+            !e.unwrap()
+                .file_name()
+                .to_string_lossy()
+                .starts_with(".alias-rs-tx-")
+        })
+    }
+
+    #[test]
+    fn commit_renames_into_place() {
+        let dir = test_dir("rename");
+        let existing = path(&dir, "existing");
+        let added = path(&dir, "sub/added");
+        fs::write(&existing, "old").unwrap();
+        let mut tx = Transaction::new();
+        tx.write(existing.clone(), b"new".to_vec());
+        tx.write(added.clone(), b"added".to_vec());
+        assert_eq!(tx.read(&existing).unwrap(), Some(b"new".to_vec()));
+        assert_eq!(
+            content(&existing),
+            Some(FileState::Content(b"old".to_vec()))
+        );
+
+        let changes = tx.commit().unwrap();
+        assert_eq!(
+            content(&existing),
+            Some(FileState::Content(b"new".to_vec()))
+        );
+        assert_eq!(content(&added), Some(FileState::Content(b"added".to_vec())));
+        assert_eq!(changes.files.len(), 2);
+        assert!(changes.files[0].before.is_some());
+        assert!(changes.files[1].before.is_none());
+        assert!(no_temp_dir(&dir));
+        assert!(no_temp_dir(&dir.join("sub")));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn commit_skips_unchanged_files() {
+        let dir = test_dir("unchanged");
+        let same = path(&dir, "same");
+        fs::write(&same, "same").unwrap();
+        let mut tx = Transaction::new();
+        tx.write(same.clone(), b"same".to_vec());
+        tx.remove(path(&dir, "absent"));
+        assert!(tx.commit().unwrap().files.is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn commit_fails_without_changes_if_staging_fails() {
+        let dir = test_dir("staging");
+        let existing = path(&dir, "existing");
+        let blocker = path(&dir, "blocker");
+        fs::write(&existing, "old").unwrap();
+        fs::write(&blocker, "a file, not a dir").unwrap();
+        let mut tx = Transaction::new();
+        tx.write(existing.clone(), b"new".to_vec());
+        tx.write(path(&dir, "blocker/under"), b"under".to_vec());
+
+        assert!(tx.commit().is_err());
+        assert_eq!(
+            content(&existing),
+            Some(FileState::Content(b"old".to_vec()))
+        );
+        assert!(no_temp_dir(&dir));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rollback_restores_after_a_failed_rename() {
+        let dir = test_dir("rollback");
+        let first = path(&dir, "first");
+        let added = path(&dir, "added");
+        let removed = path(&dir, "removed");
+        let last = path(&dir, "last");
+        fs::write(&first, "first").unwrap();
+        fs::write(&removed, "removed").unwrap();
+        fs::write(&last, "last").unwrap();
+        let mut tx = Transaction::new();
+        tx.write(first.clone(), b"first changed".to_vec());
+        tx.write(added.clone(), b"added".to_vec());
+        tx.remove(removed.clone());
+        tx.write(last.clone(), b"last changed".to_vec());
+
+        // lose the last staged file, so its rename fails after the others are applied
+        let mut temp_dirs = HashMap::new();
+        let staged = tx.prepare(&mut temp_dirs).unwrap();
+        fs::remove_file(staged[3].new_file.as_ref().unwrap()).unwrap();
+        let mut applied = Vec::new();
+        assert!(Transaction::apply(&staged, &mut applied).is_err());
+        Transaction::rollback(applied);
+
+        assert_eq!(content(&first), Some(FileState::Content(b"first".to_vec())));
+        assert_eq!(content(&added), None);
+        assert_eq!(
+            content(&removed),
+            Some(FileState::Content(b"removed".to_vec()))
+        );
+        assert_eq!(content(&last), Some(FileState::Content(b"last".to_vec())));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn commit_removes_files() {
+        let dir = test_dir("remove");
+        let removed = path(&dir, "removed");
+        fs::write(&removed, "removed").unwrap();
+        let mut tx = Transaction::new();
+        tx.remove(removed.clone());
+        assert_eq!(tx.state(&removed).unwrap(), None);
+
+        let changes = tx.commit().unwrap();
+        assert_eq!(content(&removed), None);
+        assert!(changes.files[0].before.is_some());
+        assert!(changes.files[0].after.is_none());
+        assert!(no_temp_dir(&dir));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_goes_through_links() {
+        let dir = test_dir("through-link");
+        let target = path(&dir, "managed/setting.toml");
+        let link = path(&dir, "setting.toml");
+        fs::create_dir_all(dir.join("managed")).unwrap();
+        fs::write(&target, "old").unwrap();
+        files::symlink(&target, &link).unwrap();
+        let mut tx = Transaction::new();
+        assert_eq!(tx.read(&link).unwrap(), Some(b"old".to_vec()));
+        tx.write(link.clone(), b"new".to_vec());
+        assert_eq!(tx.read(&link).unwrap(), Some(b"new".to_vec()));
+
+        let changes = tx.commit().unwrap();
+        assert_eq!(content(&link), Some(FileState::Link(target.clone())));
+        assert_eq!(content(&target), Some(FileState::Content(b"new".to_vec())));
+        assert_eq!(
+            changes.files[0].path,
+            fs::canonicalize(&target).unwrap().to_string_lossy()
+        );
+        assert!(no_temp_dir(&dir));
+        assert!(no_temp_dir(&dir.join("managed")));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_keeps_the_mode() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = test_dir("mode");
+        let script = path(&dir, "script.sh");
+        let private = path(&dir, "private");
+        fs::write(&script, "old").unwrap();
+        fs::write(&private, "old").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::set_permissions(&private, fs::Permissions::from_mode(0o600)).unwrap();
+        let mut tx = Transaction::new();
+        tx.write(script.clone(), b"new".to_vec());
+        tx.write(private.clone(), b"new".to_vec());
+        tx.commit().unwrap();
+
+        let mode = |path: &str| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&script), 0o755);
+        assert_eq!(mode(&private), 0o600);
+        assert_eq!(content(&script), Some(FileState::Content(b"new".to_vec())));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn commit_replaces_links_not_their_targets() {
+        let dir = test_dir("link");
+        let target = path(&dir, "target");
+        let other = path(&dir, "other");
+        let link = path(&dir, "link");
+        fs::write(&target, "target").unwrap();
+        fs::write(&other, "other").unwrap();
+        let mut tx = Transaction::new();
+        tx.link(link.clone(), target.clone());
+        tx.commit().unwrap();
+        assert_eq!(content(&link), Some(FileState::Link(target.clone())));
+
+        let mut tx = Transaction::new();
+        tx.link(link.clone(), other.clone());
+        tx.commit().unwrap();
+        assert_eq!(content(&link), Some(FileState::Link(other.clone())));
+        assert_eq!(
+            content(&target),
+            Some(FileState::Content(b"target".to_vec()))
+        );
+
+        let mut tx = Transaction::new();
+        tx.remove(link.clone());
+        tx.commit().unwrap();
+        assert_eq!(content(&link), None);
+        assert_eq!(content(&other), Some(FileState::Content(b"other".to_vec())));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
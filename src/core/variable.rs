@@ -0,0 +1,199 @@
+use super::error::{AliasError, ErrorKind};
+use indexmap::IndexMap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    fs,
+    path::Path,
+};
+
+#[derive(Debug, Clone)]
+pub enum VariableSource {
+    /// `[variables]` table of the setting file
+    Setting(String),
+    /// file given by `--define-file`
+    DefineFile(String),
+    /// `--define` argument, or the process environment for a bare `--define key`
+    Define,
+}
+
+impl Display for VariableSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VariableSource::Setting(path) => write!(f, "setting :: {}", path),
+            VariableSource::DefineFile(path) => write!(f, "define file :: {}", path),
+            VariableSource::Define => write!(f, "--define"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Variable {
+    pub value: String,
+    pub source: VariableSource,
+}
+
+/// Variables given on the command line, merged over the setting's `[variables]` table
+/// with the precedence: `--define` > `--define-file` > setting.
+#[derive(Debug, Clone, Default)]
+pub struct RuntimeVariables {
+    defines: HashMap<String, String>,
+    define_file: Option<(String, HashMap<String, String>)>,
+}
+
+impl RuntimeVariables {
+    pub fn new(
+        defines: HashMap<String, String>,
+        define_file_path: Option<String>,
+    ) -> Result<Self, AliasError> {
+        let define_file = match define_file_path {
+            Some(path) => {
+                let variables = load_define_file(&path)?;
+                Some((path, variables))
+            }
+            None => None,
+        };
+        Ok(Self {
+            defines,
+            define_file,
+        })
+    }
+
+    /// All the variables given, in a stable order, to tell if they changed
+    pub fn fingerprint(&self) -> String {
+        let mut defines: Vec<_> = self.defines.iter().collect();
+        defines.sort();
+        let define_file = self.define_file.as_ref().map(|(path, variables)| {
+            let mut variables: Vec<_> = variables.iter().collect();
+            variables.sort();
+            (path, variables)
+        });
+        format!("{:?}{:?}", defines, define_file)
+    }
+
+    /// `setting_variables` are `(setting path, variables)` of each setting file,
+    /// from the lowest precedence to the highest
+    pub fn resolve(
+        &self,
+        setting_variables: Vec<(String, IndexMap<String, String>)>,
+    ) -> BTreeMap<String, Variable> {
+        let mut resolved = BTreeMap::new();
+        for (setting_path, variables) in setting_variables {
+            for (name, value) in variables {
+                resolved.insert(
+                    name,
+                    Variable {
+                        value,
+                        source: VariableSource::Setting(setting_path.clone()),
+                    },
+                );
+            }
+        }
+        if let Some((path, variables)) = &self.define_file {
+            for (name, value) in variables {
+                resolved.insert(
+                    name.clone(),
+                    Variable {
+                        value: value.clone(),
+                        source: VariableSource::DefineFile(path.clone()),
+                    },
+                );
+            }
+        }
+        for (name, value) in &self.defines {
+            resolved.insert(
+                name.clone(),
+                Variable {
+                    value: value.clone(),
+                    source: VariableSource::Define,
+                },
+            );
+        }
+        resolved
+    }
+}
+
+/// Load variables from a `.toml` file (top level keys) or a `.env` file (`KEY=VALUE` lines)
+fn load_define_file(path: &String) -> Result<HashMap<String, String>, AliasError> {
+    let content = fs::read_to_string(path).map_err(|e| AliasError {
+        kind: ErrorKind::Unkonw,
+        msg: format!("read define file fail :: {} :: {}", path, e),
+    })?;
+    let is_toml = Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"));
+    if is_toml {
+        parse_toml_define_file(path, &content)
+    } else {
+        parse_env_define_file(path, &content)
+    }
+}
+
+fn parse_toml_define_file(
+    path: &String,
+    content: &str,
+) -> Result<HashMap<String, String>, AliasError> {
+    let table: toml::Table = toml::from_str(content).map_err(|e| AliasError {
+        kind: ErrorKind::Unkonw,
+        msg: format!("deserialize define file fail :: {} :: {}", path, e),
+    })?;
+    let mut map = HashMap::new();
+    for (name, value) in table {
+        let value = match value {
+            toml::Value::String(s) => s,
+            toml::Value::Table(_) | toml::Value::Array(_) => {
+                return Err(AliasError {
+                    kind: ErrorKind::Unkonw,
+                    msg: format!(
+                        "define file value should be a string, number or boolean :: {} :: {}",
+                        path, name
+                    ),
+                })
+            }
+            other => other.to_string(),
+        };
+        map.insert(name, value);
+    }
+    Ok(map)
+}
+
+fn parse_env_define_file(
+    path: &String,
+    content: &str,
+) -> Result<HashMap<String, String>, AliasError> {
+    let mut map = HashMap::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        match line.split_once('=') {
+            Some((name, value)) if !name.trim().is_empty() => {
+                map.insert(name.trim().to_owned(), unquote(value.trim()).to_owned());
+            }
+            _ => {
+                return Err(AliasError {
+                    kind: ErrorKind::Unkonw,
+                    msg: format!(
+                        "define file line should be like \"key=value\" :: {}:{} :: {}",
+                        path,
+                        index + 1,
+                        line
+                    ),
+                })
+            }
+        }
+    }
+    Ok(map)
+}
+
+/// Strip one pair of matching surrounding quotes, e.g. `"a=b"` or `'a b'`
+pub fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if value.len() >= 2 && value.starts_with(quote) && value.ends_with(quote) {
+            return &value[1..value.len() - 1];
+        }
+    }
+    value
+}
@@ -4,7 +4,18 @@ mod core;
 mod support;
 
 fn main() {
+    if let Some(result) = cli::multicall::dispatch() {
+        match result {
+            Ok(code) => std::process::exit(code),
+            Err(e) => {
+                println!("got some trouble, err={:?}, msg={}", e.kind, e.msg);
+                std::process::exit(1);
+            }
+        }
+    }
+    cli::completion::complete();
     if let Err(e) = cli::parse() {
         println!("got some trouble, err={:?}, msg={}", e.kind, e.msg);
+        std::process::exit(1);
     }
 }
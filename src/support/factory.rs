@@ -1,15 +1,16 @@
-use super::{linux::LinuxAlias, macos::MacosAlias, windows::WindowsAlias};
+use super::{linux::LinuxAlias, macos::MacosAlias, unix_like_base, windows, windows::WindowsAlias};
 use crate::core::{
     alias::Alias,
     alias_import::{AliasImport, AliasImporter},
     error::AliasError,
+    variable::RuntimeVariables,
 };
 use env::consts::OS;
-use std::{collections::HashMap, env, rc::Rc};
+use std::{env, rc::Rc};
 
 pub fn get_alias(
     setting_path: &Option<String>,
-    runtime_variables: &HashMap<String, String>,
+    runtime_variables: &RuntimeVariables,
 ) -> Result<Option<Box<dyn Alias>>, AliasError> {
     Ok(if OS == "macos" {
         Some(Box::new(MacosAlias::new(setting_path, runtime_variables)?))
@@ -25,9 +26,43 @@ pub fn get_alias(
     })
 }
 
+/// Run an alias command directly, the same as its script does, return the exit code
+pub fn run_alias(alias: &str, command: &str, args: &[String]) -> Result<i32, AliasError> {
+    match OS {
+        "windows" => windows::run(alias, command, args),
+        _ => unix_like_base::run(alias, command, args),
+    }
+}
+
+/// Run an alias command in place of alias-rs, as a multicall link does, return the exit code
+/// where the process can not be replaced (Windows)
+pub fn exec_alias(alias: &str, command: &str, args: &[String]) -> Result<i32, AliasError> {
+    #[cfg(unix)]
+    return Err(unix_like_base::exec(alias, command, args));
+    #[cfg(not(unix))]
+    return run_alias(alias, command, args);
+}
+
+/// System setting path of the platform, merged beneath the user setting
+pub fn get_system_setting_path() -> String {
+    match OS {
+        "windows" => windows::get_system_setting_path(),
+        _ => unix_like_base::get_system_setting_path(),
+    }
+}
+
+/// User setting path, the given one or the platform default
+pub fn get_setting_path(setting_path: &Option<String>) -> String {
+    match setting_path {
+        Some(setting_path) => setting_path.to_owned(),
+        None if OS == "windows" => windows::get_default_setting_path(),
+        None => unix_like_base::get_default_setting_path(),
+    }
+}
+
 pub fn get_alias_importer(
     setting_path: &Option<String>,
-    runtime_variables: &HashMap<String, String>,
+    runtime_variables: &RuntimeVariables,
 ) -> Result<Option<Box<dyn AliasImport>>, AliasError> {
     Ok(
         if let Some(alias) = get_alias(setting_path, runtime_variables)? {
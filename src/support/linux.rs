@@ -1,6 +1,14 @@
 use super::unix_like_base::UnixLikeAlias;
-use crate::core::{alias::Alias, alias_setting::AliasSetting, error::AliasError};
-use std::collections::HashMap;
+use crate::core::{
+    alias::Alias,
+    alias_setting::{AliasKind, AliasSetting},
+    backup::EnvSnapshot,
+    error::AliasError,
+    lock::DirLock,
+    transaction::Transaction,
+    variable::RuntimeVariables,
+};
+use std::time::Duration;
 
 pub struct LinuxAlias {
     unix_like_base: UnixLikeAlias,
@@ -9,7 +17,7 @@ pub struct LinuxAlias {
 impl LinuxAlias {
     pub fn new(
         setting_path: &Option<String>,
-        runtime_variables: &HashMap<String, String>,
+        runtime_variables: &RuntimeVariables,
     ) -> Result<Self, AliasError> {
         Ok(Self {
             unix_like_base: UnixLikeAlias::new(setting_path, runtime_variables)?,
@@ -18,23 +26,49 @@ impl LinuxAlias {
 }
 
 impl Alias for LinuxAlias {
-    fn init(&self) -> Result<(), AliasError> {
-        self.unix_like_base.init()
+    fn stage_init(&self, tx: &mut Transaction) -> Result<(), AliasError> {
+        self.unix_like_base.stage_init(tx)
+    }
+
+    fn snapshot_env(&self) -> Result<EnvSnapshot, AliasError> {
+        self.unix_like_base.snapshot_env()
+    }
+
+    fn stage_env_restore(
+        &self,
+        tx: &mut Transaction,
+        snapshot: &EnvSnapshot,
+    ) -> Result<(), AliasError> {
+        self.unix_like_base.stage_env_restore(tx, snapshot)
     }
 
     fn setting(&self) -> AliasSetting {
         self.unix_like_base.setting()
     }
 
-    fn set(&self, alias: String, command: String) -> Result<(), AliasError> {
-        self.unix_like_base.set(alias, command)
+    fn setting_path(&self) -> String {
+        self.unix_like_base.setting_path()
     }
 
-    fn remove(&self, alias: String) -> Result<(), AliasError> {
-        self.unix_like_base.remove(alias)
+    fn stage_script(
+        &self,
+        tx: &mut Transaction,
+        alias: &str,
+        command: &str,
+        kind: AliasKind,
+    ) -> Result<(), AliasError> {
+        self.unix_like_base.stage_script(tx, alias, command, kind)
+    }
+
+    fn stage_script_removal(&self, tx: &mut Transaction, alias: &str) -> Result<(), AliasError> {
+        self.unix_like_base.stage_script_removal(tx, alias)
     }
 
     fn list(&self) -> Result<Option<Vec<String>>, AliasError> {
         self.unix_like_base.list()
     }
+
+    fn lock(&self, wait: Option<Duration>) -> Result<Vec<DirLock>, AliasError> {
+        self.unix_like_base.lock(wait)
+    }
 }
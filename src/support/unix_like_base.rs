@@ -1,21 +1,30 @@
 use crate::{
+    cli::multicall,
     cmn::{files, unix_like},
     core::{
-        alias::Alias,
-        alias_setting::{self, AliasSetting},
+        alias::{self, Alias},
+        alias_setting::{self, AliasKind, AliasSetting, ScriptMode},
+        backup::{EnvSnapshot, ProfileSnapshot},
         error::{AliasError, ErrorKind},
+        lock::{self, DirLock},
+        transaction::Transaction,
+        variable::RuntimeVariables,
     },
 };
+use regex::Regex;
 use std::{
-    collections::HashMap,
-    fs::File,
-    io::{Read, Write},
-    process::Command,
+    io::Read,
+    ops::Range,
+    process::{Command, ExitStatus},
+    time::Duration,
 };
 
 const DEFAULT_HOME: &str = ".alias-rs";
 const DEFAULT_SCRIPT_HOME_NAME: &str = "script";
 const DEFAULT_SETTING_NAME: &str = "alias-setting.toml";
+const SYSTEM_HOME: &str = "/etc/alias-rs";
+const PROFILE_BLOCK_START: &str = "# alias-rs :: start";
+const PROFILE_BLOCK_END: &str = "# alias-rs :: end";
 
 pub fn get_default_home() -> String {
     unix_like::get_home() + "/" + DEFAULT_HOME
@@ -29,109 +38,299 @@ pub fn get_default_setting_path() -> String {
     get_default_home() + "/" + DEFAULT_SETTING_NAME
 }
 
+pub fn get_system_setting_path() -> String {
+    SYSTEM_HOME.to_owned() + "/" + DEFAULT_SETTING_NAME
+}
+
+/// Byte range of the alias-rs block in a shell profile, markers included
+fn find_profile_block(profile_content: &str) -> Option<Range<usize>> {
+    let start = profile_content.find(PROFILE_BLOCK_START)?;
+    let end = start + profile_content[start..].find(PROFILE_BLOCK_END)? + PROFILE_BLOCK_END.len();
+    Some(start..end)
+}
+
+fn read_profile() -> Result<(String, String), AliasError> {
+    let (profile_path, mut profile) = unix_like::get_shell_profile()?;
+    let mut profile_content = String::new();
+    if let Err(e) = profile.read_to_string(&mut profile_content) {
+        return Err(AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!("read shell profile fail :: {}", e),
+        });
+    }
+    Ok((profile_path, profile_content))
+}
+
+/// The alias-rs block of a shell profile, adding the script home to PATH
+fn profile_block(script_home: &str) -> String {
+    format!(
+        "{}\nexport PATH=$PATH:{}\n{}",
+        PROFILE_BLOCK_START, script_home, PROFILE_BLOCK_END
+    )
+}
+
+/// Append the block adding the script home to PATH to a shell profile, it takes effect in
+/// new shells. A linked profile (e.g. from a dotfiles repo) is written through the link
+fn stage_profile_block(
+    tx: &mut Transaction,
+    profile_path: &str,
+    script_home: &str,
+) -> Result<(), AliasError> {
+    let mut profile_content = match tx.read(profile_path)? {
+        Some(content) => String::from_utf8_lossy(&content).to_string(),
+        None => String::default(),
+    };
+    let source_script_home_cmd = profile_block(script_home);
+    if profile_content.contains(&source_script_home_cmd) {
+        return Ok(());
+    }
+    profile_content.push_str("\n\n");
+    profile_content.push_str(&source_script_home_cmd);
+    tx.write(profile_path.to_owned(), profile_content.into_bytes());
+    Ok(())
+}
+
+/// `sh -c` running an alias command the same as its script does
+fn shell_command(alias: &str, command: &str, args: &[String]) -> Command {
+    // arguments are appended, unless the command takes them itself
+    let uses_args = Regex::new(r"\$(\{?[0-9@*#])").unwrap().is_match(command);
+    let command = match uses_args {
+        true => command.to_owned(),
+        false => format!("{} \"$@\"", command),
+    };
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command).arg(alias).args(args);
+    shell
+}
+
+/// Run an alias command directly, the same as its script does, return the exit code
+pub fn run(alias: &str, command: &str, args: &[String]) -> Result<i32, AliasError> {
+    let status = shell_command(alias, command, args)
+        .status()
+        .map_err(|e| AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!("run alias fail :: {} :: {}", alias, e),
+        })?;
+    Ok(exit_code(status))
+}
+
+/// Replace this process with an alias command, as a multicall link runs it, so signals and the
+/// process tree are the same as running the command itself. A command without shell syntax is
+/// executed directly, others by `sh -c`. Return only if it can not be executed
+#[cfg(unix)]
+pub fn exec(alias: &str, command: &str, args: &[String]) -> AliasError {
+    use std::os::unix::process::CommandExt;
+    let words: Vec<&str> = command.split_whitespace().collect();
+    let error = match words.split_first() {
+        Some((program, words)) if !alias::has_shell_syntax(command) => {
+            Command::new(program).args(words).args(args).exec()
+        }
+        _ => shell_command(alias, command, args).exec(),
+    };
+    AliasError {
+        kind: ErrorKind::Unkonw,
+        msg: format!("run alias fail :: {} :: {}", alias, error),
+    }
+}
+
+/// Exit code of a process, a process killed by a signal gets the same code as shells give
+fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+        return 128 + signal;
+    }
+    status.code().unwrap_or(1)
+}
+
 pub struct UnixLikeAlias {
+    pub setting_path: String,
     pub setting: AliasSetting,
 }
 
 impl UnixLikeAlias {
     pub fn new(
         setting_path: &Option<String>,
-        runtime_variables: &HashMap<String, String>,
+        runtime_variables: &RuntimeVariables,
     ) -> Result<Self, AliasError> {
         let setting_path = setting_path
             .as_ref()
             .map_or(get_default_setting_path(), |f| f.to_owned());
-        let mut setting = alias_setting::load(&setting_path, &runtime_variables)?;
+        let mut setting =
+            alias_setting::load(&get_system_setting_path(), &setting_path, runtime_variables)?;
         if setting.script.home.is_none() {
             setting.script.home = Some(get_default_script_home());
         }
-        Ok(Self { setting })
+        Ok(Self {
+            setting_path,
+            setting,
+        })
     }
 
-    fn build_alias_script_path(&self, alias: &String) -> String {
+    fn build_alias_script_path(&self, alias: &str) -> String {
         format!(
             "{}/{}.sh",
             self.setting.script.home.as_ref().unwrap(),
             alias
         )
     }
+
+    /// link to the alias-rs binary, in multicall mode
+    fn build_alias_link_path(&self, alias: &str) -> String {
+        format!("{}/{}", self.setting.script.home.as_ref().unwrap(), alias)
+    }
 }
 
 impl Alias for UnixLikeAlias {
-    fn init(&self) -> Result<(), AliasError> {
-        // read profile
-        let (profile_path, mut profile) = unix_like::get_shell_profile()?;
-        let mut profile_content = String::new();
-        if let Err(e) = profile.read_to_string(&mut profile_content) {
-            return Err(AliasError {
-                kind: ErrorKind::Unkonw,
-                msg: format!("read shell profile fail :: {}", e),
-            });
-        }
-        // set script home
-        let source_script_home_cmd = format!(
-            "# alias-rs :: start\nexport PATH=$PATH:{}\n# alias-rs :: end",
-            &self.setting.script.home.as_ref().unwrap()
-        );
-        if profile_content.contains(&source_script_home_cmd) {
-            return Ok(());
-        }
-        profile_content.push_str("\n\n");
-        profile_content.push_str(&source_script_home_cmd);
-        // overwrite profile
-        File::create(profile_path.clone())
-            .and_then(|mut f| f.write_all(profile_content.as_bytes()))
-            .map_err(|e| AliasError {
-                kind: ErrorKind::Unkonw,
-                msg: format!("overwrite profile fail :: {}", e),
-            })?;
-        // source profile
-        match Command::new("source").arg(profile_path).status() {
-            Ok(exit_status) => match exit_status.success() {
-                true => Ok(()),
-                false => Err(AliasError {
-                    kind: ErrorKind::Unkonw,
-                    msg: format!(
-                        "source shell profile fail :: exit_code={}",
-                        exit_status.code().unwrap_or(-999)
-                    ),
-                }),
-            },
-            Err(e) => Err(AliasError {
-                kind: ErrorKind::Unkonw,
-                msg: format!("source shell profile fail :: {}", e),
+    fn stage_init(&self, tx: &mut Transaction) -> Result<(), AliasError> {
+        let (profile_path, _) = read_profile()?;
+        stage_profile_block(
+            tx,
+            &profile_path,
+            self.setting.script.home.as_ref().unwrap(),
+        )
+    }
+
+    fn snapshot_env(&self) -> Result<EnvSnapshot, AliasError> {
+        let (profile_path, profile_content) = read_profile()?;
+        Ok(EnvSnapshot {
+            profile: Some(ProfileSnapshot {
+                path: profile_path,
+                block: find_profile_block(&profile_content)
+                    .map(|range| profile_content[range].to_owned()),
             }),
+            vars: Vec::new(),
+        })
+    }
+
+    fn stage_env_restore(
+        &self,
+        tx: &mut Transaction,
+        snapshot: &EnvSnapshot,
+    ) -> Result<(), AliasError> {
+        let Some(profile) = &snapshot.profile else {
+            return Ok(());
+        };
+        // the profile and the block in the backup are not replayed, as the backup may come
+        // from another home, only whether there was a block is restored, for the current ones
+        let (profile_path, _) = read_profile()?;
+        let block = profile
+            .block
+            .as_ref()
+            .map(|_| profile_block(self.setting.script.home.as_ref().unwrap()));
+        let mut profile_content = match tx.read(&profile_path)? {
+            Some(content) => String::from_utf8_lossy(&content).to_string(),
+            None => String::default(),
+        };
+        match (find_profile_block(&profile_content), &block) {
+            (Some(range), Some(block)) => profile_content.replace_range(range, block),
+            (Some(range), None) => {
+                // also remove the blank line `init` put before the block
+                let start = match profile_content[..range.start].ends_with("\n\n") {
+                    true => range.start - 2,
+                    false => range.start,
+                };
+                profile_content.replace_range(start..range.end, "");
+            }
+            (None, Some(block)) => {
+                profile_content.push_str("\n\n");
+                profile_content.push_str(block);
+            }
+            (None, None) => return Ok(()),
         }
+        tx.write(profile_path, profile_content.into_bytes());
+        Ok(())
     }
 
     fn setting(&self) -> AliasSetting {
         self.setting.clone()
     }
 
-    fn set(&self, alias: String, command: String) -> Result<(), AliasError> {
-        let alias_script_path = self.build_alias_script_path(&alias);
-        files::create_with_all_dir(&alias_script_path)
-            .and_then(|mut f| f.write_all(command.as_bytes()))
-            .map_err(|e| AliasError {
-                kind: ErrorKind::Unkonw,
-                msg: format!("create alias script fail :: {}", e),
-            })?;
+    fn setting_path(&self) -> String {
+        self.setting_path.clone()
+    }
+
+    fn stage_script(
+        &self,
+        tx: &mut Transaction,
+        alias: &str,
+        command: &str,
+        kind: AliasKind,
+    ) -> Result<(), AliasError> {
+        let alias_script_path = self.build_alias_script_path(alias);
+        let alias_link_path = self.build_alias_link_path(alias);
+        let script_home = self.setting.script.home.as_ref().unwrap();
+        let link_target = alias::link_target(alias, command, kind, script_home)?;
+        match (link_target, self.setting.script.mode.unwrap_or_default()) {
+            (Some(target), _) => {
+                tx.link(alias_link_path, target);
+                tx.remove(alias_script_path);
+            }
+            (None, ScriptMode::Script) => {
+                tx.write(alias_script_path, command.as_bytes().to_vec());
+                tx.remove(alias_link_path);
+            }
+            (None, ScriptMode::Multicall) => {
+                tx.link(alias_link_path, multicall::get_binary_path()?);
+                tx.remove(alias_script_path);
+            }
+        }
         Ok(())
     }
 
-    fn remove(&self, alias: String) -> Result<(), AliasError> {
-        let alias_script_path = self.build_alias_script_path(&alias);
-        files::remove_if_present(&alias_script_path).map_err(|e| AliasError {
-            kind: ErrorKind::Unkonw,
-            msg: format!("remove alias script fail :: {}", e),
-        })?;
+    fn stage_script_removal(&self, tx: &mut Transaction, alias: &str) -> Result<(), AliasError> {
+        tx.remove(self.build_alias_script_path(alias));
+        tx.remove(self.build_alias_link_path(alias));
         Ok(())
     }
 
     fn list(&self) -> Result<Option<Vec<String>>, AliasError> {
-        files::list_dir(&self.setting.script.home.as_ref().unwrap()).map_err(|e| AliasError {
+        files::list_dir(self.setting.script.home.as_ref().unwrap()).map_err(|e| AliasError {
             kind: ErrorKind::Unkonw,
             msg: format!("list alias script fail :: {}", e),
         })
     }
+
+    fn lock(&self, wait: Option<Duration>) -> Result<Vec<DirLock>, AliasError> {
+        lock::lock_dirs(
+            &[
+                alias_setting::get_setting_dir(&self.setting_path),
+                self.setting.script.home.clone().unwrap(),
+            ],
+            wait,
+        )
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::{env, fs, os::unix::fs::PermissionsExt, process};
+
+    #[test]
+    fn init_writes_a_linked_profile_through_the_link() {
+        let dir = env::temp_dir().join(format!("alias-rs-profile-test-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("dotfiles")).unwrap();
+        let managed = dir.join("dotfiles/bashrc");
+        let profile = dir.join(".bashrc").to_string_lossy().to_string();
+        fs::write(&managed, "# managed\n").unwrap();
+        fs::set_permissions(&managed, fs::Permissions::from_mode(0o600)).unwrap();
+        files::symlink(&managed.to_string_lossy(), &profile).unwrap();
+
+        for _ in 0..2 {
+            let mut tx = Transaction::new();
+            stage_profile_block(&mut tx, &profile, "/opt/alias-rs/script").unwrap();
+            tx.commit().unwrap();
+        }
+        assert!(fs::symlink_metadata(&profile).unwrap().is_symlink());
+        assert_eq!(
+            fs::metadata(&managed).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        let content = fs::read_to_string(&managed).unwrap();
+        assert!(content.starts_with("# managed\n"));
+        assert_eq!(content.matches(PROFILE_BLOCK_START).count(), 1);
+        assert!(content.contains("export PATH=$PATH:/opt/alias-rs/script"));
+        let _ = fs::remove_dir_all(&dir);
+    }
 }
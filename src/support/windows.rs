@@ -1,17 +1,24 @@
 use crate::{
+    cli::multicall,
     cmn::{files, windows_like},
     core::{
-        alias::Alias,
-        alias_setting::{self, AliasSetting},
+        alias::{self, Alias},
+        alias_setting::{self, AliasKind, AliasSetting, ScriptMode},
+        backup::EnvSnapshot,
         error::{AliasError, ErrorKind},
+        lock::{self, DirLock},
+        transaction::Transaction,
+        variable::RuntimeVariables,
     },
 };
-use std::collections::HashMap;
+
+use std::{process::Command, time::Duration};
 
 const DEFAULT_HOME: &str = "alias-rs";
 const DEFAULT_SCRIPT_HOME_NAME: &str = "script";
 const DEFAULT_SCRIPT_HOME_ENV_NAME: &str = "ALIAS_SCRIPT_HOME";
 const DEFAULT_SETTING_NAME: &str = "alias-setting.toml";
+const PATH_ENV_NAME: &str = "Path";
 
 pub fn get_default_home() -> String {
     windows_like::get_local_app_home() + "\\" + DEFAULT_HOME
@@ -25,61 +32,153 @@ pub fn get_default_setting_path() -> String {
     get_default_home() + "\\" + DEFAULT_SETTING_NAME
 }
 
+pub fn get_system_setting_path() -> String {
+    windows_like::get_program_data_home() + "\\" + DEFAULT_HOME + "\\" + DEFAULT_SETTING_NAME
+}
+
+/// Run an alias command directly, the same as its bat script does, return the exit code
+pub fn run(alias: &str, command: &str, args: &[String]) -> Result<i32, AliasError> {
+    // arguments are appended as PowerShell strings
+    let args: Vec<String> = args
+        .iter()
+        .map(|arg| format!("'{}'", arg.replace('\'', "''")))
+        .collect();
+    let status = Command::new("PowerShell")
+        .args(["-ExecutionPolicy", "Bypass", "-Command"])
+        .arg(format!("{} {}", command, args.join(" ")))
+        .status()
+        .map_err(|e| AliasError {
+            kind: ErrorKind::Unkonw,
+            msg: format!("run alias fail :: {} :: {}", alias, e),
+        })?;
+    Ok(status.code().unwrap_or(1))
+}
+
 pub struct WindowsAlias {
+    pub setting_path: String,
     pub setting: AliasSetting,
 }
 
 impl WindowsAlias {
     pub fn new(
         setting_path: &Option<String>,
-        runtime_variables: &HashMap<String, String>,
+        runtime_variables: &RuntimeVariables,
     ) -> Result<Self, AliasError> {
         let setting_path = setting_path
             .as_ref()
             .map_or(get_default_setting_path(), |f| f.to_owned());
-        let mut setting = alias_setting::load(&setting_path, &runtime_variables)?;
+        let mut setting =
+            alias_setting::load(&get_system_setting_path(), &setting_path, runtime_variables)?;
         if setting.script.home.is_none() {
             setting.script.home = Some(get_default_script_home());
         }
         if setting.script.home_env_name.is_none() {
             setting.script.home_env_name = Some(DEFAULT_SCRIPT_HOME_ENV_NAME.to_owned())
         }
-        Ok(Self { setting })
+        Ok(Self {
+            setting_path,
+            setting,
+        })
     }
 
-    fn build_alias_script_path(&self, alias: &String) -> String {
+    fn build_alias_script_path(&self, alias: &str) -> String {
         format!(
             "{}\\{}.bat",
             self.setting.script.home.as_ref().unwrap(),
             alias
         )
     }
+
+    /// link to the alias-rs binary, in multicall mode
+    fn build_alias_link_path(&self, alias: &str) -> String {
+        format!(
+            "{}\\{}.exe",
+            self.setting.script.home.as_ref().unwrap(),
+            alias
+        )
+    }
 }
 
 impl Alias for WindowsAlias {
-    fn init(&self) -> Result<(), AliasError> {
+    fn stage_init(&self, tx: &mut Transaction) -> Result<(), AliasError> {
         // set 'script home' env
         let home_name = self.setting.script.home_env_name.as_ref().unwrap();
         let home_value = self.setting.script.home.as_ref().unwrap();
-        let old_home_value = windows_like::get_user_env_var(&home_name)?;
-        if old_home_value.is_none() || &old_home_value.unwrap() != home_value {
-            windows_like::set_user_env_var(home_name.clone(), home_value.clone())?;
+        let old_home_value = windows_like::get_user_env_var(home_name)?;
+        if old_home_value.as_ref() != Some(home_value) {
+            tx.set_user_env(home_name.clone(), old_home_value, Some(home_value.clone()));
         }
         // set 'Path' env
-        let path_name = "Path".to_owned();
+        let path_name = PATH_ENV_NAME.to_owned();
         let home_var_placeholder = format!("%{}%", home_name);
         match windows_like::get_user_env_var(&path_name)? {
             Some(old_var_value) => {
                 if !old_var_value.contains(&home_var_placeholder) {
-                    windows_like::set_user_env_var(
-                        path_name,
-                        format!("{};{}", old_var_value, home_var_placeholder),
-                    )?;
+                    let new_var_value = format!("{};{}", old_var_value, home_var_placeholder);
+                    tx.set_user_env(path_name, Some(old_var_value), Some(new_var_value));
                 }
             }
             None => {
-                windows_like::set_user_env_var(path_name, home_var_placeholder)?;
+                tx.set_user_env(path_name, None, Some(home_var_placeholder));
+            }
+        }
+        Ok(())
+    }
+
+    fn snapshot_env(&self) -> Result<EnvSnapshot, AliasError> {
+        let home_name = self.setting.script.home_env_name.clone().unwrap();
+        let path_name = PATH_ENV_NAME.to_owned();
+        Ok(EnvSnapshot {
+            profile: None,
+            vars: vec![
+                (
+                    home_name.clone(),
+                    windows_like::get_user_env_var(&home_name)?,
+                ),
+                (
+                    path_name.clone(),
+                    windows_like::get_user_env_var(&path_name)?,
+                ),
+            ],
+        })
+    }
+
+    fn stage_env_restore(
+        &self,
+        tx: &mut Transaction,
+        snapshot: &EnvSnapshot,
+    ) -> Result<(), AliasError> {
+        let home_name = self.setting.script.home_env_name.clone().unwrap();
+        let home_var_placeholder = format!("%{}%", home_name);
+        // the values in the backup are not replayed, as the backup may come from another home,
+        // only whether the script home was set is restored, for the current script home
+        for (name, value) in &snapshot.vars {
+            if *name != home_name && name != PATH_ENV_NAME {
+                continue;
             }
+            let current = windows_like::get_user_env_var(name)?;
+            if *name == home_name {
+                let restored = value
+                    .as_ref()
+                    .map(|_| self.setting.script.home.clone().unwrap());
+                tx.set_user_env(name.clone(), current, restored);
+                continue;
+            }
+            // only the script home entry of 'Path' is restored, other entries are kept
+            let keep_home = value
+                .as_ref()
+                .is_some_and(|v| v.contains(&home_var_placeholder));
+            let mut entries: Vec<String> = current
+                .iter()
+                .flat_map(|v| v.split(';'))
+                .filter(|e| !e.is_empty() && *e != home_var_placeholder)
+                .map(|e| e.to_owned())
+                .collect();
+            if keep_home {
+                entries.push(home_var_placeholder.clone());
+            }
+            let restored = Some(entries.join(";")).filter(|v| !v.is_empty());
+            tx.set_user_env(name.clone(), current, restored);
         }
         Ok(())
     }
@@ -88,29 +187,62 @@ impl Alias for WindowsAlias {
         self.setting.clone()
     }
 
-    fn set(&self, alias: String, command: String) -> Result<(), AliasError> {
-        let alias_script_path = self.build_alias_script_path(&alias);
+    fn setting_path(&self) -> String {
+        self.setting_path.clone()
+    }
+
+    fn stage_script(
+        &self,
+        tx: &mut Transaction,
+        alias: &str,
+        command: &str,
+        kind: AliasKind,
+    ) -> Result<(), AliasError> {
+        let alias_script_path = self.build_alias_script_path(alias);
         let bat_script = format!(
             "PowerShell -ExecutionPolicy Bypass -Command {} ^$args",
-            windows_like::convert_to_bat_str_arg(command)
+            windows_like::convert_to_bat_str_arg(command.to_owned())
         );
-        windows_like::create_ansi_file(&alias_script_path, &bat_script)?;
+        let alias_link_path = self.build_alias_link_path(alias);
+        let script_home = self.setting.script.home.as_ref().unwrap();
+        let link_target = alias::link_target(alias, command, kind, script_home)?;
+        match (link_target, self.setting.script.mode.unwrap_or_default()) {
+            (Some(target), _) => {
+                tx.link(alias_link_path, target);
+                tx.remove(alias_script_path);
+            }
+            (None, ScriptMode::Script) => {
+                tx.write(alias_script_path, windows_like::encode_ansi(&bat_script));
+                tx.remove(alias_link_path);
+            }
+            (None, ScriptMode::Multicall) => {
+                tx.link(alias_link_path, multicall::get_binary_path()?);
+                tx.remove(alias_script_path);
+            }
+        }
         Ok(())
     }
 
-    fn remove(&self, alias: String) -> Result<(), AliasError> {
-        let alias_script_path = self.build_alias_script_path(&alias);
-        files::remove_if_present(&alias_script_path).map_err(|e| AliasError {
-            kind: ErrorKind::Unkonw,
-            msg: format!("remove alias script fail :: {}", e),
-        })?;
+    fn stage_script_removal(&self, tx: &mut Transaction, alias: &str) -> Result<(), AliasError> {
+        tx.remove(self.build_alias_script_path(alias));
+        tx.remove(self.build_alias_link_path(alias));
         Ok(())
     }
 
     fn list(&self) -> Result<Option<Vec<String>>, AliasError> {
-        files::list_dir(&self.setting.script.home.as_ref().unwrap()).map_err(|e| AliasError {
+        files::list_dir(self.setting.script.home.as_ref().unwrap()).map_err(|e| AliasError {
             kind: ErrorKind::Unkonw,
             msg: format!("list alias script fail :: {}", e),
         })
     }
+
+    fn lock(&self, wait: Option<Duration>) -> Result<Vec<DirLock>, AliasError> {
+        lock::lock_dirs(
+            &[
+                alias_setting::get_setting_dir(&self.setting_path),
+                self.setting.script.home.clone().unwrap(),
+            ],
+            wait,
+        )
+    }
 }